@@ -0,0 +1,104 @@
+use std::{collections::BTreeMap, env, fs, path::Path, process::Command};
+
+/// Output of a command, `None` if it can't be run or fails.
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// `<name> <version>` of every direct dependency as locked in `Cargo.lock`,
+/// comma separated. The lockfile isn't tracked, without one this is empty
+/// and the build warns.
+fn locked_crates() -> String {
+    let lockfile = match fs::read_to_string("Cargo.lock") {
+        Ok(lockfile) => lockfile,
+        Err(e) => {
+            println!(
+                "cargo:warning=cannot read Cargo.lock ({}), the result metadata won't list \
+                 the crate versions",
+                e
+            );
+            return String::new();
+        }
+    };
+    let package = env::var("CARGO_PKG_NAME").unwrap_or_default();
+
+    let mut versions: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    let mut direct = Vec::new();
+
+    for entry in lockfile.split("[[package]]").skip(1) {
+        let value = |key: &str| {
+            entry.lines().find_map(|line| {
+                let value = line.strip_prefix(key)?.trim_start().strip_prefix('=')?;
+                Some(value.trim().trim_matches('"'))
+            })
+        };
+
+        let (name, version) = match (value("name"), value("version")) {
+            (Some(name), Some(version)) => (name, version),
+            _ => continue,
+        };
+        versions.entry(name).or_default().push(version);
+
+        if name == package {
+            direct = entry
+                .lines()
+                .skip_while(|line| !line.starts_with("dependencies"))
+                .skip(1)
+                .take_while(|line| !line.starts_with(']'))
+                .map(|line| line.trim().trim_end_matches(',').trim_matches('"'))
+                .collect();
+        }
+    }
+
+    let crates: Vec<_> = direct
+        .iter()
+        .map(|dependency| {
+            // Entries carry a version only if several are locked.
+            let mut parts = dependency.split(' ');
+            let name = parts.next().unwrap_or_default();
+            match parts
+                .next()
+                .or_else(|| versions.get(name)?.first().copied())
+            {
+                Some(version) => format!("{} {}", name, version),
+                None => name.to_string(),
+            }
+        })
+        .collect();
+    crates.join(",")
+}
+
+fn main() {
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".into());
+    let rustc_version = output(&rustc, &["-V"]).unwrap_or_default();
+    let commit = output("git", &["rev-parse", "HEAD"]).unwrap_or_default();
+    let dirty = output("git", &["status", "--porcelain", "--untracked-files=no"])
+        .is_some_and(|status| !status.is_empty());
+
+    println!("cargo:rustc-env=BUILD_RUSTC_VERSION={}", rustc_version);
+    println!("cargo:rustc-env=BUILD_GIT_COMMIT={}", commit);
+    println!("cargo:rustc-env=BUILD_GIT_DIRTY={}", dirty);
+    println!(
+        "cargo:rustc-env=BUILD_PROFILE={}",
+        env::var("PROFILE").unwrap_or_default()
+    );
+    println!(
+        "cargo:rustc-env=BUILD_OPT_LEVEL={}",
+        env::var("OPT_LEVEL").unwrap_or_default()
+    );
+    println!("cargo:rustc-env=BUILD_CRATES={}", locked_crates());
+
+    for path in [".git/HEAD", ".git/index", ".git/refs/heads"] {
+        if Path::new(path).exists() {
+            println!("cargo:rerun-if-changed={}", path);
+        }
+    }
+    // Also while missing, so that the crate versions are picked up once
+    // there is a lockfile.
+    println!("cargo:rerun-if-changed=Cargo.lock");
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -3,34 +3,14 @@
 set -x
 
 BIN=./target/release/conc-map-bench
-OUT=./results
+SUITE=${1:-./suites/default.json}
 
 cargo build --release
-mkdir -p "$OUT"
 
-function bench {
-    ARGS=$3
-    date
-
-    file="$OUT/$1.$2.csv"
-
-    if [ -s "$file" ]; then
-        ARGS+=" --csv-no-headers"
-    fi
-
-    skip=$(cat "$file" | cut -d, -f1 | uniq | paste -sd ' ' -)
-
-    if ! "$BIN" bench -w $1 -h $2 $ARGS --skip $skip --csv 2>>"$file"; then
-        bench "$1" "$2" "$3"
-    fi
-}
-
-bench ReadHeavy std
-bench Exchange std
-bench RapidGrow std
+date
 
-bench ReadHeavy ahash
-bench Exchange ahash
-bench RapidGrow ahash
+# Maps run in child processes, a crashing map is recorded and skipped. An
+# interrupted run continues where it stopped when started again.
+"$BIN" suite "$SUITE"
 
 date
@@ -8,12 +8,36 @@ DATA_DIR=results
 cargo build --release
 
 function plot {
-    cat "$DATA_DIR/$1.csv" | "$BIN" plot "$DATA_DIR" "$1"
+    "$BIN" plot "$DATA_DIR" "$1" --input "$DATA_DIR/$1.csv"
+}
+
+function plot_phases {
+    for phase in fill steady churn; do
+        "$BIN" plot "$DATA_DIR" "$1.$phase" --input "$DATA_DIR/$1.csv" --phase "$phase"
+    done
+}
+
+# Both hot key counts together, drawing fairness over the count.
+function plot_hot_keys {
+    "$BIN" plot "$DATA_DIR" "HotKeys.all.$1" \
+        --input "$DATA_DIR/HotKeys.$1.csv" --input "$DATA_DIR/HotKeys16.$1.csv"
 }
 
 plot ReadHeavy.std
 plot Exchange.std
 plot RapidGrow.std
+plot Aggregate.std
+plot GetOrInsert.std
+plot HotKeys.std
+plot HotKeys16.std
+plot_hot_keys std
+plot_phases Phased.std
 plot ReadHeavy.ahash
 plot Exchange.ahash
 plot RapidGrow.ahash
+plot Aggregate.ahash
+plot GetOrInsert.ahash
+plot HotKeys.ahash
+plot HotKeys16.ahash
+plot_hot_keys ahash
+plot_phases Phased.ahash
@@ -5,14 +5,15 @@ use bustle::*;
 use parking_lot::RwLock;
 use std::sync::RwLock as StdRwLock;
 
-use super::Value;
+use super::{Upsert, Value};
 
 #[derive(Clone)]
-pub struct ParkingLotRwLockBTreeMapTable<K>(Arc<RwLock<BTreeMap<K, Value>>>);
+pub struct ParkingLotRwLockBTreeMapTable<K, V>(Arc<RwLock<BTreeMap<K, V>>>);
 
-impl<K> Collection for ParkingLotRwLockBTreeMapTable<K>
+impl<K, V> Collection for ParkingLotRwLockBTreeMapTable<K, V>
 where
-    K: Send + Sync + From<u64> + Copy + 'static + Ord,
+    K: Send + Sync + From<u64> + Clone + 'static + Ord,
+    V: Value,
 {
     type Handle = Self;
 
@@ -25,9 +26,10 @@ where
     }
 }
 
-impl<K> CollectionHandle for ParkingLotRwLockBTreeMapTable<K>
+impl<K, V> CollectionHandle for ParkingLotRwLockBTreeMapTable<K, V>
 where
-    K: Send + Sync + From<u64> + Copy + 'static + Ord,
+    K: Send + Sync + From<u64> + Clone + 'static + Ord,
+    V: Value,
 {
     type Key = K;
 
@@ -36,7 +38,7 @@ where
     }
 
     fn insert(&mut self, key: &Self::Key) -> bool {
-        self.0.write().insert(*key, 0).is_none()
+        self.0.write().insert(key.clone(), V::default()).is_none()
     }
 
     fn remove(&mut self, key: &Self::Key) -> bool {
@@ -45,16 +47,36 @@ where
 
     fn update(&mut self, key: &Self::Key) -> bool {
         let mut map = self.0.write();
-        map.get_mut(key).map(|v| *v += 1).is_some()
+        map.get_mut(key).map(|v| v.update()).is_some()
+    }
+}
+
+impl<K, V> Upsert for ParkingLotRwLockBTreeMapTable<K, V>
+where
+    K: Send + Sync + From<u64> + Clone + 'static + Ord,
+    V: Value,
+{
+    fn upsert(&mut self, key: &Self::Key) -> bool {
+        let mut present = false;
+        self.0
+            .write()
+            .entry(key.clone())
+            .and_modify(|v| {
+                v.update();
+                present = true;
+            })
+            .or_default();
+        present
     }
 }
 
 #[derive(Clone)]
-pub struct StdRwLockBTreeMapTable<K>(Arc<StdRwLock<BTreeMap<K, Value>>>);
+pub struct StdRwLockBTreeMapTable<K, V>(Arc<StdRwLock<BTreeMap<K, V>>>);
 
-impl<K> Collection for StdRwLockBTreeMapTable<K>
+impl<K, V> Collection for StdRwLockBTreeMapTable<K, V>
 where
-    K: Send + Sync + From<u64> + Copy + 'static + Ord,
+    K: Send + Sync + From<u64> + Clone + 'static + Ord,
+    V: Value,
 {
     type Handle = Self;
 
@@ -67,9 +89,10 @@ where
     }
 }
 
-impl<K> CollectionHandle for StdRwLockBTreeMapTable<K>
+impl<K, V> CollectionHandle for StdRwLockBTreeMapTable<K, V>
 where
-    K: Send + Sync + From<u64> + Copy + 'static + Ord,
+    K: Send + Sync + From<u64> + Clone + 'static + Ord,
+    V: Value,
 {
     type Key = K;
 
@@ -78,7 +101,11 @@ where
     }
 
     fn insert(&mut self, key: &Self::Key) -> bool {
-        self.0.write().unwrap().insert(*key, 0).is_none()
+        self.0
+            .write()
+            .unwrap()
+            .insert(key.clone(), V::default())
+            .is_none()
     }
 
     fn remove(&mut self, key: &Self::Key) -> bool {
@@ -90,7 +117,27 @@ where
             .write()
             .unwrap()
             .get_mut(key)
-            .map(|v| *v += 1)
+            .map(|v| v.update())
             .is_some()
     }
 }
+
+impl<K, V> Upsert for StdRwLockBTreeMapTable<K, V>
+where
+    K: Send + Sync + From<u64> + Clone + 'static + Ord,
+    V: Value,
+{
+    fn upsert(&mut self, key: &Self::Key) -> bool {
+        let mut present = false;
+        self.0
+            .write()
+            .unwrap()
+            .entry(key.clone())
+            .and_modify(|v| {
+                v.update();
+                present = true;
+            })
+            .or_default();
+        present
+    }
+}
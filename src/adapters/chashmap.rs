@@ -3,14 +3,15 @@ use std::{fmt::Debug, hash::Hash, sync::Arc};
 use bustle::*;
 use chashmap::CHashMap;
 
-use super::Value;
+use super::{Upsert, Value};
 
 #[derive(Clone)]
-pub struct CHashMapTable<K>(Arc<CHashMap<K, Value>>);
+pub struct CHashMapTable<K, V>(Arc<CHashMap<K, V>>);
 
-impl<K> Collection for CHashMapTable<K>
+impl<K, V> Collection for CHashMapTable<K, V>
 where
-    K: Send + Sync + From<u64> + Copy + 'static + Hash + Eq + Debug,
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Eq + Debug,
+    V: Value,
 {
     type Handle = Self;
 
@@ -23,9 +24,10 @@ where
     }
 }
 
-impl<K> CollectionHandle for CHashMapTable<K>
+impl<K, V> CollectionHandle for CHashMapTable<K, V>
 where
-    K: Send + Sync + From<u64> + Copy + 'static + Hash + Eq + Debug,
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Eq + Debug,
+    V: Value,
 {
     type Key = K;
 
@@ -34,7 +36,7 @@ where
     }
 
     fn insert(&mut self, key: &Self::Key) -> bool {
-        self.0.insert(*key, 0).is_none()
+        self.0.insert(key.clone(), V::default()).is_none()
     }
 
     fn remove(&mut self, key: &Self::Key) -> bool {
@@ -45,8 +47,27 @@ where
         self.0
             .get_mut(key)
             .map(|mut r| {
-                *r += 1;
+                r.update();
             })
             .is_some()
     }
 }
+
+impl<K, V> Upsert for CHashMapTable<K, V>
+where
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Eq + Debug,
+    V: Value,
+{
+    fn upsert(&mut self, key: &Self::Key) -> bool {
+        let mut present = true;
+        self.0.upsert(
+            key.clone(),
+            || {
+                present = false;
+                V::default()
+            },
+            |v| v.update(),
+        );
+        present
+    }
+}
@@ -5,14 +5,15 @@ use bustle::*;
 use contrie::ConMap;
 use parking_lot::Mutex;
 
-use super::Value;
+use super::{Upsert, Value};
 
 #[derive(Clone)]
-pub struct ContrieTable<K: Eq + Hash + 'static, H>(Arc<ConMap<K, Mutex<Value>, H>>);
+pub struct ContrieTable<K: Eq + Hash + 'static, V, H>(Arc<ConMap<K, Mutex<V>, H>>);
 
-impl<K, H> Collection for ContrieTable<K, H>
+impl<K, V, H> Collection for ContrieTable<K, V, H>
 where
-    K: Send + Sync + From<u64> + Copy + 'static + Hash + Eq + Debug,
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Eq + Debug,
+    V: Value,
     H: BuildHasher + Default + Send + Sync + 'static + Clone,
 {
     type Handle = Self;
@@ -26,9 +27,10 @@ where
     }
 }
 
-impl<K, H> CollectionHandle for ContrieTable<K, H>
+impl<K, V, H> CollectionHandle for ContrieTable<K, V, H>
 where
-    K: Send + Sync + From<u64> + Copy + 'static + Hash + Eq + Debug,
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Eq + Debug,
+    V: Value,
     H: BuildHasher + Default + Send + Sync + 'static + Clone,
 {
     type Key = K;
@@ -38,7 +40,9 @@ where
     }
 
     fn insert(&mut self, key: &Self::Key) -> bool {
-        self.0.insert(*key, Mutex::new(0)).is_none()
+        self.0
+            .insert(key.clone(), Mutex::new(V::default()))
+            .is_none()
     }
 
     fn remove(&mut self, key: &Self::Key) -> bool {
@@ -49,8 +53,27 @@ where
         self.0
             .get(key)
             .map(|e| {
-                *e.value().lock() += 1;
+                e.value().lock().update();
             })
             .is_some()
     }
 }
+
+impl<K, V, H> Upsert for ContrieTable<K, V, H>
+where
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Eq + Debug,
+    V: Value,
+    H: BuildHasher + Default + Send + Sync + 'static + Clone,
+{
+    fn upsert(&mut self, key: &Self::Key) -> bool {
+        let mut present = true;
+        let element = self.0.get_or_insert_with(key.clone(), || {
+            present = false;
+            Mutex::new(V::default())
+        });
+        if present {
+            element.value().lock().update();
+        }
+        present
+    }
+}
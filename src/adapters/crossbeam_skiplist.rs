@@ -4,13 +4,14 @@ use bustle::*;
 use crossbeam_skiplist::SkipMap;
 use parking_lot::Mutex;
 
-use super::Value;
+use super::{Upsert, Value};
 
-pub struct CrossbeamSkipMapTable<K>(Arc<SkipMap<K, Mutex<Value>>>);
+pub struct CrossbeamSkipMapTable<K, V>(Arc<SkipMap<K, Mutex<V>>>);
 
-impl<K> Collection for CrossbeamSkipMapTable<K>
+impl<K, V> Collection for CrossbeamSkipMapTable<K, V>
 where
-    K: Send + Sync + From<u64> + Copy + Ord + 'static,
+    K: Send + Sync + From<u64> + Clone + Ord + 'static,
+    V: Value,
 {
     type Handle = Self;
 
@@ -23,9 +24,10 @@ where
     }
 }
 
-impl<K> CollectionHandle for CrossbeamSkipMapTable<K>
+impl<K, V> CollectionHandle for CrossbeamSkipMapTable<K, V>
 where
-    K: Send + Sync + From<u64> + Copy + 'static + Ord,
+    K: Send + Sync + From<u64> + Clone + 'static + Ord,
+    V: Value,
 {
     type Key = K;
 
@@ -36,7 +38,7 @@ where
     fn insert(&mut self, key: &Self::Key) -> bool {
         let map = &mut self.0;
         let prev = map.get(key).is_none();
-        map.insert(*key, Mutex::new(0));
+        map.insert(key.clone(), Mutex::new(V::default()));
         prev
     }
 
@@ -48,8 +50,26 @@ where
         self.0
             .get(key)
             .map(|e| {
-                *e.value().lock() += 1;
+                e.value().lock().update();
             })
             .is_some()
     }
 }
+
+impl<K, V> Upsert for CrossbeamSkipMapTable<K, V>
+where
+    K: Send + Sync + From<u64> + Clone + 'static + Ord,
+    V: Value,
+{
+    fn upsert(&mut self, key: &Self::Key) -> bool {
+        let mut present = true;
+        let entry = self.0.get_or_insert_with(key.clone(), || {
+            present = false;
+            Mutex::new(V::default())
+        });
+        if present {
+            entry.value().lock().update();
+        }
+        present
+    }
+}
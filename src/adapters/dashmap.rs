@@ -2,16 +2,17 @@ use std::hash::{BuildHasher, Hash};
 use std::sync::Arc;
 
 use bustle::*;
-use dashmap::DashMap;
+use dashmap::{mapref::entry::Entry, DashMap};
 
-use super::Value;
+use super::{Upsert, Value};
 
 #[derive(Clone)]
-pub struct DashMapTable<K, H>(Arc<DashMap<K, Value, H>>);
+pub struct DashMapTable<K, V, H>(Arc<DashMap<K, V, H>>);
 
-impl<K, H> Collection for DashMapTable<K, H>
+impl<K, V, H> Collection for DashMapTable<K, V, H>
 where
-    K: Send + Sync + From<u64> + Copy + 'static + Hash + Eq + std::fmt::Debug,
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Eq + std::fmt::Debug,
+    V: Value,
     H: BuildHasher + Default + Send + Sync + 'static + Clone,
 {
     type Handle = Self;
@@ -28,9 +29,10 @@ where
     }
 }
 
-impl<K, H> CollectionHandle for DashMapTable<K, H>
+impl<K, V, H> CollectionHandle for DashMapTable<K, V, H>
 where
-    K: Send + Sync + From<u64> + Copy + 'static + Hash + Eq + std::fmt::Debug,
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Eq + std::fmt::Debug,
+    V: Value,
     H: BuildHasher + Default + Send + Sync + 'static + Clone,
 {
     type Key = K;
@@ -40,7 +42,7 @@ where
     }
 
     fn insert(&mut self, key: &Self::Key) -> bool {
-        self.0.insert(*key, 0).is_none()
+        self.0.insert(key.clone(), V::default()).is_none()
     }
 
     fn remove(&mut self, key: &Self::Key) -> bool {
@@ -48,6 +50,26 @@ where
     }
 
     fn update(&mut self, key: &Self::Key) -> bool {
-        self.0.get_mut(key).map(|mut v| *v += 1).is_some()
+        self.0.get_mut(key).map(|mut v| v.update()).is_some()
+    }
+}
+
+impl<K, V, H> Upsert for DashMapTable<K, V, H>
+where
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Eq + std::fmt::Debug,
+    V: Value,
+    H: BuildHasher + Default + Send + Sync + 'static + Clone,
+{
+    fn upsert(&mut self, key: &Self::Key) -> bool {
+        match self.0.entry(key.clone()) {
+            Entry::Occupied(mut entry) => {
+                entry.get_mut().update();
+                true
+            }
+            Entry::Vacant(entry) => {
+                entry.insert(V::default());
+                false
+            }
+        }
     }
 }
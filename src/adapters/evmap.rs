@@ -1,23 +1,51 @@
 use std::hash::{BuildHasher, Hash};
+use std::mem::ManuallyDrop;
 use std::sync::Arc;
 
 use bustle::*;
+use evmap::shallow_copy::ShallowCopy;
 use parking_lot::Mutex;
 
-use super::Value;
+use super::{Upsert, Value};
+use crate::values::{ArcValue, BoxValue, InlineValue, StringValue};
+
+impl<const N: usize> ShallowCopy for InlineValue<N> {
+    unsafe fn shallow_copy(&self) -> ManuallyDrop<Self> {
+        ManuallyDrop::new(*self)
+    }
+}
+
+impl ShallowCopy for BoxValue {
+    unsafe fn shallow_copy(&self) -> ManuallyDrop<Self> {
+        ManuallyDrop::new(BoxValue(ManuallyDrop::into_inner(self.0.shallow_copy())))
+    }
+}
+
+impl ShallowCopy for ArcValue {
+    unsafe fn shallow_copy(&self) -> ManuallyDrop<Self> {
+        ManuallyDrop::new(ArcValue(ManuallyDrop::into_inner(self.0.shallow_copy())))
+    }
+}
+
+impl ShallowCopy for StringValue {
+    unsafe fn shallow_copy(&self) -> ManuallyDrop<Self> {
+        ManuallyDrop::new(StringValue(ManuallyDrop::into_inner(self.0.shallow_copy())))
+    }
+}
 
 #[derive(Clone)]
-pub struct EvmapTable<K: Hash + Eq + Clone, H: BuildHasher + Clone> {
-    rd: Arc<Mutex<evmap::ReadHandle<K, Value, (), H>>>,
-    wr: Arc<Mutex<evmap::WriteHandle<K, Value, (), H>>>,
+pub struct EvmapTable<K: Hash + Eq + Clone, V: Value + ShallowCopy, H: BuildHasher + Clone> {
+    rd: Arc<Mutex<evmap::ReadHandle<K, V, (), H>>>,
+    wr: Arc<Mutex<evmap::WriteHandle<K, V, (), H>>>,
 }
 
-impl<K, H> Collection for EvmapTable<K, H>
+impl<K, V, H> Collection for EvmapTable<K, V, H>
 where
-    K: Send + Sync + From<u64> + Copy + 'static + Hash + Eq,
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Eq,
+    V: Value + ShallowCopy,
     H: BuildHasher + Default + Send + Sync + 'static + Clone,
 {
-    type Handle = EvmapTableHandle<K, H>;
+    type Handle = EvmapTableHandle<K, V, H>;
 
     fn with_capacity(capacity: usize) -> Self {
         let (rd, wr) = evmap::Options::default()
@@ -39,14 +67,15 @@ where
     }
 }
 
-pub struct EvmapTableHandle<K: Hash + Eq + Clone, H: BuildHasher + Clone> {
-    rd: evmap::ReadHandle<K, u32, (), H>,
-    wr: Arc<Mutex<evmap::WriteHandle<K, u32, (), H>>>,
+pub struct EvmapTableHandle<K: Hash + Eq + Clone, V: Value + ShallowCopy, H: BuildHasher + Clone> {
+    rd: evmap::ReadHandle<K, V, (), H>,
+    wr: Arc<Mutex<evmap::WriteHandle<K, V, (), H>>>,
 }
 
-impl<K, H> CollectionHandle for EvmapTableHandle<K, H>
+impl<K, V, H> CollectionHandle for EvmapTableHandle<K, V, H>
 where
-    K: Send + Sync + From<u64> + Copy + 'static + Hash + Eq,
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Eq,
+    V: Value + ShallowCopy,
     H: BuildHasher + Default + Send + Sync + 'static + Clone,
 {
     type Key = K;
@@ -57,24 +86,47 @@ where
 
     fn insert(&mut self, key: &Self::Key) -> bool {
         let prev = self.rd.get_one(key).is_none();
-        self.wr.lock().insert(*key, 0).refresh();
+        self.wr.lock().insert(key.clone(), V::default()).refresh();
         prev
     }
 
     fn remove(&mut self, key: &Self::Key) -> bool {
         let prev = self.rd.get_one(key).is_some();
-        self.wr.lock().empty(*key).refresh();
+        self.wr.lock().empty(key.clone()).refresh();
         prev
     }
 
     fn update(&mut self, key: &Self::Key) -> bool {
         let val = match self.rd.get_one(key) {
-            Some(val) => *val + 1,
+            Some(val) => val.updated(),
             None => return false,
         };
 
         let prev = self.rd.get_one(key).is_some();
-        self.wr.lock().update(*key, val).refresh();
+        self.wr.lock().update(key.clone(), val).refresh();
         prev
     }
 }
+
+impl<K, V, H> Upsert for EvmapTableHandle<K, V, H>
+where
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Eq,
+    V: Value + ShallowCopy,
+    H: BuildHasher + Default + Send + Sync + 'static + Clone,
+{
+    /// evmap has a single writer, so holding it across the lookup makes the
+    /// upsert atomic.
+    fn upsert(&mut self, key: &Self::Key) -> bool {
+        let mut wr = self.wr.lock();
+        match self.rd.get_one(key).map(|val| val.updated()) {
+            Some(val) => {
+                wr.update(key.clone(), val).refresh();
+                true
+            }
+            None => {
+                wr.insert(key.clone(), V::default()).refresh();
+                false
+            }
+        }
+    }
+}
@@ -3,16 +3,21 @@ use seize::Collector;
 use std::hash::{BuildHasher, Hash};
 use std::sync::Arc;
 
-use super::Value;
+use super::{Upsert, Value};
 
 const BATCH_SIZE: usize = 2000;
 
+/// Rounds of `compute_if_present` and `try_insert` an upsert races through
+/// before giving up on updating the value in place.
+const UPSERT_ATTEMPTS: usize = 16;
+
 #[derive(Clone)]
-pub struct FlurryTable<K: 'static, H: 'static>(Arc<flurry::HashMap<K, Value, H>>);
+pub struct FlurryTable<K: 'static, V: 'static, H: 'static>(Arc<flurry::HashMap<K, V, H>>);
 
-impl<K, H> Collection for FlurryTable<K, H>
+impl<K, V, H> Collection for FlurryTable<K, V, H>
 where
-    K: Send + Sync + From<u64> + Copy + 'static + Hash + Ord,
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Ord,
+    V: Value,
     H: BuildHasher + Default + Send + Sync + 'static + Clone,
 {
     type Handle = Self;
@@ -32,9 +37,10 @@ where
     }
 }
 
-impl<K, H> CollectionHandle for FlurryTable<K, H>
+impl<K, V, H> CollectionHandle for FlurryTable<K, V, H>
 where
-    K: Send + Sync + From<u64> + Copy + 'static + Hash + Ord,
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Ord,
+    V: Value,
     H: BuildHasher + Default + Send + Sync + 'static + Clone,
 {
     type Key = K;
@@ -44,7 +50,7 @@ where
     }
 
     fn insert(&mut self, key: &Self::Key) -> bool {
-        self.0.pin().insert(*key, 0).is_none()
+        self.0.pin().insert(key.clone(), V::default()).is_none()
     }
 
     fn remove(&mut self, key: &Self::Key) -> bool {
@@ -54,7 +60,36 @@ where
     fn update(&mut self, key: &Self::Key) -> bool {
         self.0
             .pin()
-            .compute_if_present(key, |_, v| Some(v + 1))
+            .compute_if_present(key, |_, v| Some(v.updated()))
             .is_some()
     }
 }
+
+impl<K, V, H> Upsert for FlurryTable<K, V, H>
+where
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Ord,
+    V: Value,
+    H: BuildHasher + Default + Send + Sync + 'static + Clone,
+{
+    /// flurry has no compute that inserts, so unlike the other maps this is
+    /// not one atomic operation: a failed `compute_if_present` falls back to
+    /// `try_insert`, retrying if another thread inserted first. A retry needs
+    /// another thread to remove the key again in between, so after
+    /// `UPSERT_ATTEMPTS` of them a plain `insert` resets the value instead of
+    /// spinning, dropping the concurrent update.
+    fn upsert(&mut self, key: &Self::Key) -> bool {
+        let map = self.0.pin();
+        for _ in 0..UPSERT_ATTEMPTS {
+            if map
+                .compute_if_present(key, |_, v| Some(v.updated()))
+                .is_some()
+            {
+                return true;
+            }
+            if map.try_insert(key.clone(), V::default()).is_ok() {
+                return false;
+            }
+        }
+        map.insert(key.clone(), V::default()).is_some()
+    }
+}
@@ -15,4 +15,16 @@ mod flurry;
 mod scc;
 mod std;
 
-type Value = u32;
+use bustle::CollectionHandle;
+
+use crate::values::Value;
+
+/// Insert-or-update, which `bustle::CollectionHandle` lacks. Every map
+/// implements it with its own entry, compute or upsert API where it has one,
+/// so that the lookup and the write are a single atomic step. flurry has
+/// none and races two calls, see its adapter.
+pub trait Upsert: CollectionHandle {
+    /// Updates the value of `key`, inserting a default value if it's missing.
+    /// Returns whether `key` was present.
+    fn upsert(&mut self, key: &Self::Key) -> bool;
+}
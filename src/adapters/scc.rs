@@ -1,19 +1,20 @@
 use std::hash::{BuildHasher, Hash};
 use std::sync::Arc;
 
-use super::Value;
+use super::{Upsert, Value};
 use bustle::*;
 use scc::hash_map::{Entry, HashMap};
 
 #[derive(Clone)]
-pub struct SccMapTable<K, H>(Arc<HashMap<K, Value, H>>)
+pub struct SccMapTable<K, V, H>(Arc<HashMap<K, V, H>>)
 where
     K: Eq + Hash,
     H: BuildHasher;
 
-impl<K, H> Collection for SccMapTable<K, H>
+impl<K, V, H> Collection for SccMapTable<K, V, H>
 where
-    K: Send + Sync + From<u64> + Copy + 'static + Hash + Eq + std::fmt::Debug,
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Eq + std::fmt::Debug,
+    V: Value,
     H: BuildHasher + Default + Send + Sync + 'static + Clone,
 {
     type Handle = Self;
@@ -30,19 +31,20 @@ where
     }
 }
 
-impl<K, H> CollectionHandle for SccMapTable<K, H>
+impl<K, V, H> CollectionHandle for SccMapTable<K, V, H>
 where
-    K: Send + Sync + From<u64> + Copy + 'static + Hash + Eq + std::fmt::Debug,
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Eq + std::fmt::Debug,
+    V: Value,
     H: BuildHasher + Default + Send + Sync + 'static + Clone,
 {
     type Key = K;
 
     fn get(&mut self, key: &Self::Key) -> bool {
-        self.0.read(key, |_, v| *v).is_some()
+        self.0.read(key, |_, _| ()).is_some()
     }
 
     fn insert(&mut self, key: &Self::Key) -> bool {
-        self.0.insert(*key, 0).is_ok()
+        self.0.insert(key.clone(), V::default()).is_ok()
     }
 
     fn remove(&mut self, key: &Self::Key) -> bool {
@@ -50,12 +52,32 @@ where
     }
 
     fn update(&mut self, key: &Self::Key) -> bool {
-        match self.0.entry(*key) {
+        match self.0.entry(key.clone()) {
             Entry::Occupied(mut v) => {
-                *v.get_mut() += 1;
+                v.get_mut().update();
                 true
             }
             Entry::Vacant(_) => false,
         }
     }
 }
+
+impl<K, V, H> Upsert for SccMapTable<K, V, H>
+where
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Eq + std::fmt::Debug,
+    V: Value,
+    H: BuildHasher + Default + Send + Sync + 'static + Clone,
+{
+    fn upsert(&mut self, key: &Self::Key) -> bool {
+        match self.0.entry(key.clone()) {
+            Entry::Occupied(mut v) => {
+                v.get_mut().update();
+                true
+            }
+            Entry::Vacant(v) => {
+                v.insert_entry(V::default());
+                false
+            }
+        }
+    }
+}
@@ -6,14 +6,15 @@ use bustle::*;
 use parking_lot::RwLock;
 use std::sync::RwLock as StdRwLock;
 
-use super::Value;
+use super::{Upsert, Value};
 
 #[derive(Clone)]
-pub struct ParkingLotRwLockStdHashMapTable<K, H>(Arc<RwLock<HashMap<K, Value, H>>>);
+pub struct ParkingLotRwLockStdHashMapTable<K, V, H>(Arc<RwLock<HashMap<K, V, H>>>);
 
-impl<K, H> Collection for ParkingLotRwLockStdHashMapTable<K, H>
+impl<K, V, H> Collection for ParkingLotRwLockStdHashMapTable<K, V, H>
 where
-    K: Send + Sync + From<u64> + Copy + 'static + Hash + Eq,
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Eq,
+    V: Value,
     H: BuildHasher + Default + Send + Sync + 'static + Clone,
 {
     type Handle = Self;
@@ -30,9 +31,10 @@ where
     }
 }
 
-impl<K, H> CollectionHandle for ParkingLotRwLockStdHashMapTable<K, H>
+impl<K, V, H> CollectionHandle for ParkingLotRwLockStdHashMapTable<K, V, H>
 where
-    K: Send + Sync + From<u64> + Copy + 'static + Hash + Eq,
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Eq,
+    V: Value,
     H: BuildHasher + Default + Send + Sync + 'static + Clone,
 {
     type Key = K;
@@ -42,7 +44,7 @@ where
     }
 
     fn insert(&mut self, key: &Self::Key) -> bool {
-        self.0.write().insert(*key, 0).is_none()
+        self.0.write().insert(key.clone(), V::default()).is_none()
     }
 
     fn remove(&mut self, key: &Self::Key) -> bool {
@@ -51,16 +53,37 @@ where
 
     fn update(&mut self, key: &Self::Key) -> bool {
         let mut map = self.0.write();
-        map.get_mut(key).map(|v| *v += 1).is_some()
+        map.get_mut(key).map(|v| v.update()).is_some()
+    }
+}
+
+impl<K, V, H> Upsert for ParkingLotRwLockStdHashMapTable<K, V, H>
+where
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Eq,
+    V: Value,
+    H: BuildHasher + Default + Send + Sync + 'static + Clone,
+{
+    fn upsert(&mut self, key: &Self::Key) -> bool {
+        let mut present = false;
+        self.0
+            .write()
+            .entry(key.clone())
+            .and_modify(|v| {
+                v.update();
+                present = true;
+            })
+            .or_default();
+        present
     }
 }
 
 #[derive(Clone)]
-pub struct StdRwLockStdHashMapTable<K, H>(Arc<StdRwLock<HashMap<K, Value, H>>>);
+pub struct StdRwLockStdHashMapTable<K, V, H>(Arc<StdRwLock<HashMap<K, V, H>>>);
 
-impl<K, H> Collection for StdRwLockStdHashMapTable<K, H>
+impl<K, V, H> Collection for StdRwLockStdHashMapTable<K, V, H>
 where
-    K: Send + Sync + From<u64> + Copy + 'static + Hash + Eq,
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Eq,
+    V: Value,
     H: BuildHasher + Default + Send + Sync + 'static + Clone,
 {
     type Handle = Self;
@@ -77,9 +100,10 @@ where
     }
 }
 
-impl<K, H> CollectionHandle for StdRwLockStdHashMapTable<K, H>
+impl<K, V, H> CollectionHandle for StdRwLockStdHashMapTable<K, V, H>
 where
-    K: Send + Sync + From<u64> + Copy + 'static + Hash + Eq,
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Eq,
+    V: Value,
     H: BuildHasher + Default + Send + Sync + 'static + Clone,
 {
     type Key = K;
@@ -89,7 +113,11 @@ where
     }
 
     fn insert(&mut self, key: &Self::Key) -> bool {
-        self.0.write().unwrap().insert(*key, 0).is_none()
+        self.0
+            .write()
+            .unwrap()
+            .insert(key.clone(), V::default())
+            .is_none()
     }
 
     fn remove(&mut self, key: &Self::Key) -> bool {
@@ -101,7 +129,28 @@ where
             .write()
             .unwrap()
             .get_mut(key)
-            .map(|v| *v += 1)
+            .map(|v| v.update())
             .is_some()
     }
 }
+
+impl<K, V, H> Upsert for StdRwLockStdHashMapTable<K, V, H>
+where
+    K: Send + Sync + From<u64> + Clone + 'static + Hash + Eq,
+    V: Value,
+    H: BuildHasher + Default + Send + Sync + 'static + Clone,
+{
+    fn upsert(&mut self, key: &Self::Key) -> bool {
+        let mut present = false;
+        self.0
+            .write()
+            .unwrap()
+            .entry(key.clone())
+            .and_modify(|v| {
+                v.update();
+                present = true;
+            })
+            .or_default();
+        present
+    }
+}
@@ -0,0 +1,227 @@
+use std::{collections::BTreeSet, fmt, fs, io, str::FromStr};
+
+/// Where the worker threads of a run are placed.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Pin {
+    /// Threads are left to the scheduler.
+    #[default]
+    None,
+    /// Fill the SMT siblings of a core, then the cores of a package, before
+    /// moving on to the next one.
+    Compact,
+    /// Spread threads over packages, then cores, using SMT siblings last.
+    Scatter,
+    /// Thread `i` runs on the `i`-th of these CPUs.
+    List(Vec<usize>),
+}
+
+impl fmt::Display for Pin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pin::None => write!(f, "none"),
+            Pin::Compact => write!(f, "compact"),
+            Pin::Scatter => write!(f, "scatter"),
+            Pin::List(cpus) => {
+                let cpus: Vec<_> = cpus.iter().map(usize::to_string).collect();
+                write!(f, "list:{}", cpus.join(","))
+            }
+        }
+    }
+}
+
+/// Parses a CPU list like `0-3,8,10-11`, in the order given.
+fn parse_cpu_list(list: &str) -> Result<Vec<usize>, String> {
+    let invalid = || format!("invalid cpu list '{}'", list);
+    let mut cpus = Vec::new();
+
+    for range in list.trim().split(',') {
+        match range.split_once('-') {
+            Some((first, last)) => {
+                let first: usize = first.parse().map_err(|_| invalid())?;
+                let last: usize = last.parse().map_err(|_| invalid())?;
+                if first > last {
+                    return Err(invalid());
+                }
+                cpus.extend(first..=last);
+            }
+            None => cpus.push(range.parse().map_err(|_| invalid())?),
+        }
+    }
+
+    Ok(cpus)
+}
+
+impl FromStr for Pin {
+    type Err = String;
+
+    /// Parses `none`, `compact`, `scatter` and `list:<cpus>`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("list", cpus)) => Ok(Pin::List(parse_cpu_list(cpus)?)),
+            None if s == "none" => Ok(Pin::None),
+            None if s == "compact" => Ok(Pin::Compact),
+            None if s == "scatter" => Ok(Pin::Scatter),
+            _ => Err(format!(
+                "unknown pin policy '{}', must be one of 'none', 'compact', 'scatter' or 'list:<cpus>'",
+                s
+            )),
+        }
+    }
+}
+
+/// A CPU this process may run on and where it sits in the machine.
+#[derive(Debug, Clone, Copy)]
+pub struct Cpu {
+    pub id: usize,
+    pub package: usize,
+    pub core: usize,
+}
+
+fn read_id(cpu: usize, name: &str) -> Option<usize> {
+    let path = format!("/sys/devices/system/cpu/cpu{}/topology/{}", cpu, name);
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// CPUs the process is allowed to run on, from `sched_getaffinity`.
+#[cfg(target_os = "linux")]
+fn allowed() -> Option<BTreeSet<usize>> {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        if libc::sched_getaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &mut set) != 0 {
+            return None;
+        }
+        Some(
+            (0..libc::CPU_SETSIZE as usize)
+                .filter(|&cpu| libc::CPU_ISSET(cpu, &set))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn allowed() -> Option<BTreeSet<usize>> {
+    None
+}
+
+/// The CPUs available to this process with their package and core from
+/// `/sys/devices/system/cpu`. Without sysfs every CPU counts as a core of its
+/// own.
+pub fn topology() -> Vec<Cpu> {
+    let online = fs::read_to_string("/sys/devices/system/cpu/online")
+        .ok()
+        .and_then(|list| parse_cpu_list(&list).ok())
+        .unwrap_or_else(|| (0..num_cpus::get()).collect());
+    let allowed = allowed();
+
+    online
+        .into_iter()
+        .filter(|cpu| match &allowed {
+            Some(allowed) => allowed.contains(cpu),
+            None => true,
+        })
+        .map(|id| Cpu {
+            id,
+            package: read_id(id, "physical_package_id").unwrap_or(0),
+            core: read_id(id, "core_id").unwrap_or(id),
+        })
+        .collect()
+}
+
+/// The CPUs of `topology` in the order `pin` hands them out.
+fn order(pin: &Pin, mut cpus: Vec<Cpu>) -> Vec<usize> {
+    match pin {
+        Pin::None => Vec::new(),
+        Pin::List(list) => list.clone(),
+        Pin::Compact => {
+            cpus.sort_by_key(|cpu| (cpu.package, cpu.core, cpu.id));
+            cpus.iter().map(|cpu| cpu.id).collect()
+        }
+        Pin::Scatter => {
+            cpus.sort_by_key(|cpu| (cpu.package, cpu.core, cpu.id));
+
+            // Rank of each CPU among its SMT siblings and of its core among
+            // the cores of its package.
+            let mut ranked = Vec::with_capacity(cpus.len());
+            let (mut core_rank, mut sibling) = (0, 0);
+            for (i, cpu) in cpus.iter().enumerate() {
+                if i > 0 {
+                    let prev = cpus[i - 1];
+                    if prev.package != cpu.package {
+                        core_rank = 0;
+                        sibling = 0;
+                    } else if prev.core != cpu.core {
+                        core_rank += 1;
+                        sibling = 0;
+                    } else {
+                        sibling += 1;
+                    }
+                }
+                ranked.push((sibling, core_rank, cpu.package, cpu.id));
+            }
+
+            ranked.sort_unstable();
+            ranked.iter().map(|&(_, _, _, id)| id).collect()
+        }
+    }
+}
+
+/// CPU of every one of `threads` workers, `None` if they aren't pinned. More
+/// threads than CPUs wrap around.
+pub fn placement(pin: &Pin, threads: u32) -> Option<Vec<usize>> {
+    let order = order(pin, topology());
+
+    if order.is_empty() {
+        return None;
+    }
+
+    Some(
+        order
+            .iter()
+            .copied()
+            .cycle()
+            .take(threads as usize)
+            .collect(),
+    )
+}
+
+/// Checks that `pin` can be applied on this machine.
+pub fn validate(pin: &Pin) -> Result<(), String> {
+    if *pin == Pin::None {
+        return Ok(());
+    }
+
+    if !cfg!(target_os = "linux") {
+        return Err("thread pinning is only supported on Linux".into());
+    }
+
+    if let Pin::List(list) = pin {
+        let available: BTreeSet<_> = topology().iter().map(|cpu| cpu.id).collect();
+        if let Some(cpu) = list.iter().find(|cpu| !available.contains(cpu)) {
+            return Err(format!("cpu {} is not available to this process", cpu));
+        }
+    }
+
+    Ok(())
+}
+
+/// Restricts the calling thread to `cpu`.
+#[cfg(target_os = "linux")]
+pub fn pin_thread(cpu: usize) -> io::Result<()> {
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        if libc::sched_setaffinity(0, std::mem::size_of::<libc::cpu_set_t>(), &set) == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_thread(_cpu: usize) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "thread pinning is only supported on Linux",
+    ))
+}
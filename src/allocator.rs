@@ -0,0 +1,96 @@
+use std::cell::Cell;
+
+#[cfg(feature = "count-allocations")]
+use std::alloc::{GlobalAlloc, Layout, System};
+
+/// Whether the binary was built with the `count-allocations` feature, which
+/// installs `Counting` as the global allocator.
+pub const ENABLED: bool = cfg!(feature = "count-allocations");
+
+#[cfg(feature = "count-allocations")]
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Allocator activity of one thread.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AllocCount {
+    pub allocations: u64,
+    pub deallocations: u64,
+    /// Bytes requested by allocations and reallocations.
+    pub bytes: u64,
+}
+
+impl AllocCount {
+    pub fn add(&mut self, other: &AllocCount) {
+        self.allocations += other.allocations;
+        self.deallocations += other.deallocations;
+        self.bytes += other.bytes;
+    }
+
+    /// Activity between `earlier` and `self`.
+    pub fn since(&self, earlier: &AllocCount) -> AllocCount {
+        AllocCount {
+            allocations: self.allocations - earlier.allocations,
+            deallocations: self.deallocations - earlier.deallocations,
+            bytes: self.bytes - earlier.bytes,
+        }
+    }
+}
+
+thread_local! {
+    // Const-initialized without destructors, so using them from within the
+    // allocator never allocates.
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+    static DEALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+    static BYTES: Cell<u64> = const { Cell::new(0) };
+}
+
+#[cfg(feature = "count-allocations")]
+fn bump(counter: &'static std::thread::LocalKey<Cell<u64>>, by: u64) {
+    // Fails only while the thread is being torn down, that activity is lost.
+    let _ = counter.try_with(|count| count.set(count.get() + by));
+}
+
+/// Allocator activity of the calling thread so far, zero unless `ENABLED`.
+pub fn thread_count() -> AllocCount {
+    let get = |counter: &'static std::thread::LocalKey<Cell<u64>>| {
+        counter.try_with(Cell::get).unwrap_or(0)
+    };
+
+    AllocCount {
+        allocations: get(&ALLOCATIONS),
+        deallocations: get(&DEALLOCATIONS),
+        bytes: get(&BYTES),
+    }
+}
+
+/// The system allocator, counting what every thread does with it.
+#[cfg(feature = "count-allocations")]
+pub struct Counting;
+
+#[cfg(feature = "count-allocations")]
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        bump(&ALLOCATIONS, 1);
+        bump(&BYTES, layout.size() as u64);
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        bump(&ALLOCATIONS, 1);
+        bump(&BYTES, layout.size() as u64);
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        bump(&DEALLOCATIONS, 1);
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        bump(&ALLOCATIONS, 1);
+        bump(&DEALLOCATIONS, 1);
+        bump(&BYTES, new_size as u64);
+        System.realloc(ptr, layout, new_size)
+    }
+}
@@ -1,20 +1,55 @@
-use std::collections::hash_map::RandomState;
-use std::hash::BuildHasher;
-use std::iter;
-use std::{fmt::Debug, io, thread::sleep, time::Duration};
+use std::{
+    collections::BTreeMap,
+    fs::{self, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    process,
+    thread::sleep,
+    time::Duration,
+};
 
-use bustle::*;
-use structopt::StructOpt;
+use bustle::Collection;
+use structopt::{clap, StructOpt};
 
-use crate::{adapters::*, record::Record, workloads};
+use crate::{
+    adapters::Upsert,
+    affinity::{self, Pin},
+    allocator::{self, AllocCount},
+    distribution::KeyDistribution,
+    format::{self, Format},
+    harness::{self, Measurement, Memory, Operation, OperationCount, Timeout},
+    histogram::Histogram,
+    keys::{self, KeyType},
+    metadata,
+    phases::{self, Length, Phase},
+    record::Record,
+    registry,
+    state::RunState,
+    stats::{self, Summary},
+    supervisor,
+    sweep::{self, Sweep},
+    values::{self, ValueType},
+    workloads,
+};
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HasherKind {
     Std,
     AHash,
 }
 
-fn parse_hasher_kind(hasher: &str) -> Result<HasherKind, &str> {
+impl HasherKind {
+    pub const ALL: &'static [HasherKind] = &[HasherKind::Std, HasherKind::AHash];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HasherKind::Std => "std",
+            HasherKind::AHash => "ahash",
+        }
+    }
+}
+
+pub(crate) fn parse_hasher_kind(hasher: &str) -> Result<HasherKind, &str> {
     match hasher {
         "std" => Ok(HasherKind::Std),
         "ahash" => Ok(HasherKind::AHash),
@@ -22,24 +57,193 @@ fn parse_hasher_kind(hasher: &str) -> Result<HasherKind, &str> {
     }
 }
 
-#[derive(Debug, StructOpt)]
+fn parse_map_name(name: &str) -> Result<String, String> {
+    match registry::find(name) {
+        Some(adapter) => Ok(adapter.name.into()),
+        None => Err(format!(
+            "unknown map '{}', see `conc-map-bench list` for valid names",
+            name
+        )),
+    }
+}
+
+#[derive(Debug, Clone, StructOpt)]
 pub struct Options {
     #[structopt(short, long)]
     pub workload: workloads::WorkloadKind,
+    #[structopt(flatten)]
+    pub mix: workloads::MixOptions,
+    /// Key distribution of reads, updates and upserts: uniform, zipf[:theta],
+    /// hotset[:ops/keys], sequential, latest[:theta] or hot[:keys].
+    #[structopt(long, default_value = "uniform")]
+    pub distribution: KeyDistribution,
+    /// Key type of the maps: u64, u32, string[:len], bytes16 or bytes32.
+    #[structopt(long, default_value = "u64")]
+    pub key_type: KeyType,
+    /// Value type of the maps: u32, inline:<16|64|256>, box[:len], arc[:len]
+    /// or string[:len].
+    #[structopt(long, default_value = "u32")]
+    pub value_type: ValueType,
     #[structopt(short, long, default_value = "1")]
     pub operations: f64,
+    /// Let every thread run until this much time has passed, e.g. 10s or
+    /// 500ms, instead of for `--operations`.
+    #[structopt(long, parse(try_from_str = phases::parse_time))]
+    pub duration: Option<Duration>,
+    /// Run the mix after prefilling before measuring, for a number of
+    /// operations as a multiple of the initial capacity, e.g. 0.1, or a time,
+    /// e.g. 2s.
+    #[structopt(long)]
+    pub warmup: Option<Length>,
+    /// Also record the warm-up, as phase `warmup`, to compare the cold map
+    /// with the warm one.
+    #[structopt(long, requires = "warmup")]
+    pub report_cold: bool,
+    /// A phase of the Phased workload, `<name>:<op>=<percent>,...:<length>`
+    /// with the length in operations (a multiple of the initial capacity) or
+    /// time, e.g. `churn:read=20,insert=40,remove=40:30s`. Repeat for more.
+    #[structopt(long)]
+    pub phase: Vec<Phase>,
+    /// Placement of the worker threads: none, compact, scatter or
+    /// list:<cpus>, e.g. list:0-3,8.
+    #[structopt(long, default_value = "none")]
+    pub pin: Pin,
+    /// Thread counts to measure, overriding the sweep.
     #[structopt(long)]
     pub threads: Option<Vec<u32>>,
+    /// Thread counts measured without `--threads`: linear[:step] or
+    /// geometric, up to the CPUs available to the process.
+    #[structopt(long, default_value = "linear")]
+    pub sweep: Sweep,
+    /// Add twice and four times as many threads as available CPUs to the
+    /// sweep.
+    #[structopt(long)]
+    pub oversubscribe: bool,
     #[structopt(short, long, parse(try_from_str = parse_hasher_kind))]
     pub hasher: HasherKind,
     #[structopt(long, default_value = "2000")]
     pub gc_sleep_ms: u64,
+    /// Give up on a run after this many seconds, 0 waits forever.
+    #[structopt(long, default_value = "0")]
+    pub timeout_secs: u64,
+    /// Number of times every `(map, threads)` point is measured.
+    #[structopt(long, default_value = "1")]
+    pub repetitions: u32,
+    /// Time every n-th operation into the latency histograms, 0 disables them.
+    #[structopt(long, default_value = "8")]
+    pub latency_sample: u32,
+    /// Run exactly these maps instead of the default set.
+    #[structopt(long, parse(try_from_str = parse_map_name))]
+    pub maps: Vec<String>,
+    #[structopt(long, parse(try_from_str = parse_map_name))]
+    pub skip: Vec<String>,
+    /// How records are written: text, csv, json or jsonl. Json documents
+    /// need an `--output` file, on stderr they are written as jsonl.
+    #[structopt(long, default_value = "text")]
+    pub format: Format,
+    /// Write the records to this file instead of stderr, replacing it.
+    #[structopt(long, parse(from_os_str))]
+    pub output: Option<PathBuf>,
+    /// Append to the `--output` file instead, writing CSV headers only into
+    /// an empty file.
+    #[structopt(long, requires = "output")]
+    pub append: bool,
+    /// Keep track of finished points in `<dir>/state.tsv` and skip them when
+    /// run again, appending the records to `<dir>/results.csv`.
+    #[structopt(long, parse(from_os_str))]
+    pub resume: Option<PathBuf>,
+    /// Name of the workload in the `--resume` state, telling apart runs
+    /// with the same settings. The workload kind by default.
     #[structopt(long)]
-    pub skip: Option<Vec<String>>, // TODO: use just `Vec<String>`.
+    pub name: Option<String>,
+    /// Run all maps inside this process instead of one child process per map.
     #[structopt(long)]
-    pub csv: bool,
-    #[structopt(long)]
-    pub csv_no_headers: bool,
+    pub in_process: bool,
+    /// Set on the child processes: run the given maps in-process and write
+    /// their records as CSV to this file, leaving stdout to messages.
+    #[structopt(long, hidden = true, parse(from_os_str))]
+    pub child: Option<PathBuf>,
+}
+
+impl Options {
+    /// Command line arguments of `bench` that reproduce these options.
+    pub(crate) fn to_args(&self) -> Vec<String> {
+        let mut args: Vec<String> = vec![
+            "--workload".into(),
+            self.workload.name().into(),
+            "--distribution".into(),
+            self.distribution.to_string(),
+            "--key-type".into(),
+            self.key_type.to_string(),
+            "--value-type".into(),
+            self.value_type.to_string(),
+            "--operations".into(),
+            self.operations.to_string(),
+            "--pin".into(),
+            self.pin.to_string(),
+            "--sweep".into(),
+            self.sweep.to_string(),
+            "--hasher".into(),
+            self.hasher.name().into(),
+            "--gc-sleep-ms".into(),
+            self.gc_sleep_ms.to_string(),
+            "--timeout-secs".into(),
+            self.timeout_secs.to_string(),
+            "--repetitions".into(),
+            self.repetitions.to_string(),
+            "--latency-sample".into(),
+            self.latency_sample.to_string(),
+            "--format".into(),
+            self.format.to_string(),
+        ];
+        args.extend(self.mix.to_args());
+
+        if let Some(duration) = self.duration {
+            args.extend(["--duration".into(), phases::format_time(duration)]);
+        }
+        if let Some(warmup) = self.warmup {
+            args.extend(["--warmup".into(), warmup.to_string()]);
+        }
+        for phase in &self.phase {
+            args.extend(["--phase".into(), phase.to_string()]);
+        }
+        for n in self.threads.iter().flatten() {
+            args.extend(["--threads".into(), n.to_string()]);
+        }
+        for name in &self.maps {
+            args.extend(["--maps".into(), name.clone()]);
+        }
+        for name in &self.skip {
+            args.extend(["--skip".into(), name.clone()]);
+        }
+        if let Some(path) = &self.output {
+            args.extend(["--output".into(), path.to_string_lossy().into_owned()]);
+        }
+        if let Some(dir) = &self.resume {
+            args.extend(["--resume".into(), dir.to_string_lossy().into_owned()]);
+        }
+        if let Some(path) = &self.child {
+            args.extend(["--child".into(), path.to_string_lossy().into_owned()]);
+        }
+        if let Some(name) = &self.name {
+            args.extend(["--name".into(), name.clone()]);
+        }
+
+        let flags = [
+            ("--oversubscribe", self.oversubscribe),
+            ("--append", self.append),
+            ("--report-cold", self.report_cold),
+            ("--in-process", self.in_process),
+        ];
+        args.extend(
+            flags
+                .iter()
+                .filter(|(_, set)| *set)
+                .map(|(flag, _)| flag.to_string()),
+        );
+
+        args
+    }
 }
 
 fn gc_cycle(options: &Options) {
@@ -51,106 +255,723 @@ fn gc_cycle(options: &Options) {
     }
 }
 
-type Handler = Box<dyn FnMut(&str, u32, &Measurement)>;
+pub(crate) type Handler = Box<dyn FnMut(&Record)>;
 
-fn case<C>(name: &str, options: &Options, handler: &mut Handler)
-where
+/// Exit status of a child that recorded a timed out run and gave up.
+pub(crate) const TIMED_OUT: i32 = 3;
+
+/// p50, p90, p99, p99.9 and max of a latency histogram.
+fn percentiles(histogram: &Histogram) -> [Duration; 5] {
+    [
+        histogram.quantile(0.5),
+        histogram.quantile(0.9),
+        histogram.quantile(0.99),
+        histogram.quantile(0.999),
+        histogram.max(),
+    ]
+    .map(Duration::from_nanos)
+}
+
+/// CPUs the worker threads ran on, in thread order.
+fn cpu_list(options: &Options, threads: u32) -> String {
+    let cpus = affinity::placement(&options.pin, threads).unwrap_or_default();
+    let cpus: Vec<_> = cpus.iter().map(usize::to_string).collect();
+    cpus.join(" ")
+}
+
+/// Whether `record` is the last one of its `(map, threads)` point: the one of
+/// the last phase, or a failure.
+pub(crate) fn completes(options: &Options, record: &Record) -> bool {
+    if record.status != "ok" {
+        return true;
+    }
+
+    match workloads::phases(options).last() {
+        Some(last) => record.phase == last.name,
+        None => record.phase != harness::WARMUP,
+    }
+}
+
+fn record(name: &str, options: &Options, threads: u32, runs: &[Measurement]) -> Record {
+    let throughput = Summary::of(&runs.iter().map(|m| m.throughput).collect::<Vec<_>>());
+    let latency = Summary::of(
+        &runs
+            .iter()
+            .map(|m| m.latency.as_nanos() as f64)
+            .collect::<Vec<_>>(),
+    );
+    let nanos = |ns: f64| Duration::from_nanos(ns.round() as u64);
+
+    let mut latencies: [Histogram; 5] = Default::default();
+    for m in runs {
+        for (total, latency) in latencies.iter_mut().zip(&m.latencies) {
+            total.merge(latency);
+        }
+    }
+
+    let mut all = Histogram::default();
+    for latency in &latencies {
+        all.merge(latency);
+    }
+
+    let [all_p50, all_p90, all_p99, all_p999, all_max] = percentiles(&all);
+    let [read_p50, read_p90, read_p99, read_p999, read_max] =
+        percentiles(&latencies[Operation::Read as usize]);
+    let [insert_p50, insert_p90, insert_p99, insert_p999, insert_max] =
+        percentiles(&latencies[Operation::Insert as usize]);
+    let [remove_p50, remove_p90, remove_p99, remove_p999, remove_max] =
+        percentiles(&latencies[Operation::Remove as usize]);
+    let [update_p50, update_p90, update_p99, update_p999, update_max] =
+        percentiles(&latencies[Operation::Update as usize]);
+    let [upsert_p50, upsert_p90, upsert_p99, upsert_p999, upsert_max] =
+        percentiles(&latencies[Operation::Upsert as usize]);
+
+    let mut counts = [OperationCount::default(); 5];
+    for m in runs {
+        for (total, count) in counts.iter_mut().zip(&m.counts) {
+            total.add(count);
+        }
+    }
+
+    // Operations per run, success rate, throughput and mean latency.
+    let total_ops = runs.iter().map(|m| m.total_ops).sum::<u64>();
+    let breakdown = |op: Operation| {
+        let count = counts[op as usize];
+        let per_run = count.total / runs.len() as u64;
+        let success_rate = if count.total == 0 {
+            0.0
+        } else {
+            count.succeeded as f64 / count.total as f64
+        };
+        let share = count.total as f64 / total_ops.max(1) as f64;
+        let mean = Duration::from_nanos(latencies[op as usize].mean());
+        (per_run, success_rate, throughput.mean * share, mean)
+    };
+
+    let (read_count, read_success_rate, read_throughput, read_mean) = breakdown(Operation::Read);
+    let (insert_count, insert_success_rate, insert_throughput, insert_mean) =
+        breakdown(Operation::Insert);
+    let (remove_count, remove_success_rate, remove_throughput, remove_mean) =
+        breakdown(Operation::Remove);
+    let (update_count, update_success_rate, update_throughput, update_mean) =
+        breakdown(Operation::Update);
+    let (upsert_count, upsert_success_rate, upsert_throughput, upsert_mean) =
+        breakdown(Operation::Upsert);
+
+    let mean = |field: fn(&Memory) -> u64| {
+        runs.iter().map(|m| field(&m.memory)).sum::<u64>() / runs.len() as u64
+    };
+    let rss_before = mean(|m| m.before);
+    let rss_end = mean(|m| m.end);
+    let live_entries = mean(|m| m.live_entries);
+    let bytes_per_entry = if rss_before == 0 || live_entries == 0 {
+        0.0
+    } else {
+        rss_end.saturating_sub(rss_before) as f64 / live_entries as f64
+    };
+
+    let per_op = |field: fn(&AllocCount) -> u64| {
+        runs.iter().map(|m| field(&m.allocations)).sum::<u64>() as f64 / total_ops.max(1) as f64
+    };
+
+    let per_thread = |f: fn(&[f64]) -> f64| {
+        runs.iter().map(|m| f(&m.thread_throughput)).sum::<f64>() / runs.len() as f64
+    };
+
+    Record {
+        name: name.into(),
+        total_ops: total_ops / runs.len() as u64,
+        threads,
+        spent: runs.iter().map(|m| m.spent).sum::<Duration>() / runs.len() as u32,
+        throughput: throughput.mean,
+        latency: nanos(latency.mean),
+        workload: workloads::label(options),
+        phase: runs[0].phase.clone(),
+        hasher: options.hasher.name().into(),
+        distribution: workloads::distribution(options).to_string(),
+        hot_keys: hot_keys(options),
+        key_type: options.key_type.to_string(),
+        value_type: options.value_type.to_string(),
+        pin: options.pin.to_string(),
+        cpus: cpu_list(options, threads),
+        status: "ok".into(),
+        repetitions: runs.len() as u32,
+        throughput_median: throughput.median,
+        throughput_stddev: throughput.stddev,
+        throughput_min: throughput.min,
+        throughput_max: throughput.max,
+        throughput_ci95: throughput.ci95,
+        latency_median: nanos(latency.median),
+        latency_stddev: nanos(latency.stddev),
+        latency_min: nanos(latency.min),
+        latency_max: nanos(latency.max),
+        latency_ci95: nanos(latency.ci95),
+        all_p50,
+        all_p90,
+        all_p99,
+        all_p999,
+        all_max,
+        read_p50,
+        read_p90,
+        read_p99,
+        read_p999,
+        read_max,
+        insert_p50,
+        insert_p90,
+        insert_p99,
+        insert_p999,
+        insert_max,
+        remove_p50,
+        remove_p90,
+        remove_p99,
+        remove_p999,
+        remove_max,
+        update_p50,
+        update_p90,
+        update_p99,
+        update_p999,
+        update_max,
+        upsert_p50,
+        upsert_p90,
+        upsert_p99,
+        upsert_p999,
+        upsert_max,
+        read_count,
+        read_success_rate,
+        read_throughput,
+        read_mean,
+        insert_count,
+        insert_success_rate,
+        insert_throughput,
+        insert_mean,
+        remove_count,
+        remove_success_rate,
+        remove_throughput,
+        remove_mean,
+        update_count,
+        update_success_rate,
+        update_throughput,
+        update_mean,
+        upsert_count,
+        upsert_success_rate,
+        upsert_throughput,
+        upsert_mean,
+        rss_before,
+        rss_prefilled: mean(|m| m.prefilled),
+        rss_peak: mean(|m| m.peak),
+        rss_end,
+        rss_dropped: mean(|m| m.dropped),
+        live_entries,
+        bytes_per_entry,
+        allocations_per_op: per_op(|a| a.allocations),
+        deallocations_per_op: per_op(|a| a.deallocations),
+        allocated_bytes_per_op: per_op(|a| a.bytes),
+        fairness: per_thread(stats::jain),
+        thread_throughput_min: per_thread(|t| t.iter().copied().fold(f64::INFINITY, f64::min)),
+        thread_throughput_max: per_thread(|t| t.iter().copied().fold(0.0, f64::max)),
+    }
+}
+
+/// Thread counts to measure, `--threads` or the `--sweep` up to the CPUs
+/// available to the process.
+pub(crate) fn thread_counts(options: &Options) -> Vec<u32> {
+    match &options.threads {
+        Some(threads) => threads.clone(),
+        None => sweep::thread_counts(options.sweep, options.oversubscribe),
+    }
+}
+
+pub(crate) fn case<C>(
+    name: &str,
+    options: &Options,
+    handler: &mut Handler,
+    mut state: Option<&mut RunState>,
+) where
     C: Collection,
-    <C::Handle as CollectionHandle>::Key: Send + Debug,
+    C::Handle: Upsert,
 {
-    if options
-        .skip
-        .as_ref()
-        .and_then(|s| s.iter().find(|s| s == &name))
-        .is_some()
-    {
-        println!("-- {} [skipped]", name);
-        return;
-    } else {
-        println!("-- {}", name);
+    for n in &thread_counts(options) {
+        // Repetitions measured before the run was interrupted.
+        let mut measured = match state.as_deref_mut() {
+            Some(state) => state.repetitions(options, name, *n),
+            None => BTreeMap::new(),
+        };
+
+        let mut runs = Vec::new();
+        for repetition in 1..=options.repetitions.max(1) {
+            if let Some(phases) = measured.remove(&repetition) {
+                runs.push(phases);
+                continue;
+            }
+
+            match harness::run::<C>(&workloads::create(options, *n)) {
+                Ok(phases) => {
+                    gc_cycle(options);
+                    if let Some(state) = state.as_deref_mut() {
+                        state.repeated(options, name, *n, repetition, &phases);
+                    }
+                    runs.push(phases);
+                }
+                Err(timeout) => {
+                    report_timeout(name, *n, &timeout);
+                    handler(&failed(name, options, *n, timeout.to_string()));
+
+                    // The abandoned threads can't be stopped and would
+                    // compete with every later run, the supervisor continues
+                    // with a fresh child.
+                    process::exit(TIMED_OUT);
+                }
+            }
+        }
+
+        // One record per phase over all repetitions.
+        while !runs[0].is_empty() {
+            let phase: Vec<_> = runs.iter_mut().map(|phases| phases.remove(0)).collect();
+            if phase[0].phase != harness::WARMUP || options.report_cold {
+                let record = record(name, options, *n, &phase);
+                handler(&record);
+                // The supervisor marks the points of its children done.
+                if let (Some(state), false) = (state.as_deref_mut(), options.child.is_some()) {
+                    state.recorded(options, &record);
+                }
+            }
+        }
     }
+}
 
-    let gen_threads = || {
-        let n = num_cpus::get();
+/// Tells on stderr how far every thread of a timed out run got.
+fn report_timeout(name: &str, threads: u32, timeout: &Timeout) {
+    eprintln!("watchdog: {} threads={}: {}", name, threads, timeout);
 
-        match n {
-            0..=10 => (1..=n as u32).collect(),
-            11..=16 => iter::once(1)
-                .chain((0..=n as u32).step_by(2).skip(1))
-                .collect(),
-            _ => iter::once(1)
-                .chain((0..=n as u32).step_by(4).skip(1))
-                .collect(),
+    for (thread, &progress) in timeout.progress.iter().enumerate() {
+        let state = if timeout.finished.contains(&thread) {
+            "finished"
+        } else if timeout.stalled.contains(&thread) {
+            "stalled"
+        } else {
+            "running"
+        };
+        match timeout.expected {
+            Some(expected) => eprintln!(
+                "  thread {}\t{}/{} operations\t{}",
+                thread, progress, expected, state
+            ),
+            None => eprintln!("  thread {}\t{} operations\t{}", thread, progress, state),
         }
-    };
+    }
+}
 
-    let threads = options
-        .threads
-        .as_ref()
-        .cloned()
-        .unwrap_or_else(gen_threads);
+/// Row for a `(map, threads)` point that has no measurement.
+pub(crate) fn failed(name: &str, options: &Options, threads: u32, status: String) -> Record {
+    Record {
+        name: name.into(),
+        threads,
+        workload: workloads::label(options),
+        hasher: options.hasher.name().into(),
+        distribution: workloads::distribution(options).to_string(),
+        hot_keys: hot_keys(options),
+        key_type: options.key_type.to_string(),
+        value_type: options.value_type.to_string(),
+        pin: options.pin.to_string(),
+        cpus: cpu_list(options, threads),
+        status,
+        repetitions: options.repetitions.max(1),
+        ..Record::default()
+    }
+}
 
-    for n in &threads {
-        let m = workloads::create(options, *n).run_silently::<C>();
-        handler(name, *n, &m);
-        gc_cycle(options);
+/// Number of keys the threads of a run share, see `Record::hot_keys`.
+fn hot_keys(options: &Options) -> u64 {
+    match workloads::distribution(options) {
+        KeyDistribution::Hot(keys) => keys,
+        _ => 0,
     }
-    println!();
 }
 
-fn run(options: &Options, h: &mut Handler) {
-    //case::<StdRwLockBTreeMapTable<u64>>("std:sync::RwLock<BTreeMap>", options, h);
-    //case::<ParkingLotRwLockBTreeMapTable<u64>>("parking_lot::RwLock<BTreeMap>", options, h);
-    case::<CHashMapTable<u64>>("CHashMap", options, h);
-    case::<CrossbeamSkipMapTable<u64>>("CrossbeamSkipMap", options, h);
+/// The workload with its non-default key distribution, key and value type.
+pub(crate) fn header(options: &Options) -> String {
+    let mut header = workloads::label(options);
+    let distribution = workloads::distribution(options);
+    if distribution != KeyDistribution::Uniform {
+        header = format!("{} keys={}", header, distribution);
+    }
+    if options.key_type != KeyType::U64 {
+        header = format!("{} key_type={}", header, options.key_type);
+    }
+    if options.value_type != ValueType::U32 {
+        header = format!("{} value_type={}", header, options.value_type);
+    }
+    if options.pin != Pin::None {
+        header = format!("{} pin={}", header, options.pin);
+    }
+    if let Some(duration) = options.duration {
+        header = format!("{} duration={}", header, phases::format_time(duration));
+    }
+    if let Some(warmup) = options.warmup {
+        header = format!("{} warmup={}", header, warmup);
+    }
+    header
+}
 
-    match options.hasher {
-        HasherKind::Std => run_hasher_variant::<RandomState>(options, h),
-        HasherKind::AHash => run_hasher_variant::<ahash::RandomState>(options, h),
+pub(crate) fn run(options: &Options, h: &mut Handler, mut state: Option<&mut RunState>) {
+    if options.child.is_none() {
+        println!("== {}", header(options));
+    }
+
+    if let KeyType::String(len) = options.key_type {
+        keys::set_string_len(len);
+    }
+    if let Some(len) = options.value_type.heap_len() {
+        values::set_heap_len(len);
+    }
+
+    for adapter in registry::selected(options) {
+        if options.child.is_some() {
+            let runner = adapter.runner(options.hasher, options.key_type, options.value_type);
+            runner(adapter.name, options, h, state.as_deref_mut());
+            continue;
+        }
+
+        if options.skip.iter().any(|name| name == adapter.name) {
+            println!("-- {} [skipped]", adapter.name);
+            continue;
+        }
+
+        let mut options = options.clone();
+        if let Some(state) = state.as_deref() {
+            let threads: Vec<_> = thread_counts(&options)
+                .into_iter()
+                .filter(|&n| !state.is_done(&options, adapter.name, n))
+                .collect();
+
+            if threads.is_empty() {
+                println!("-- {} [done]", adapter.name);
+                continue;
+            }
+            options.threads = Some(threads);
+        }
+
+        println!("-- {}", adapter.name);
+        if options.in_process {
+            let runner = adapter.runner(options.hasher, options.key_type, options.value_type);
+            runner(adapter.name, &options, h, state.as_deref_mut());
+        } else {
+            supervisor::case(adapter.name, &options, h, state.as_deref_mut());
+        }
+        println!();
     }
 }
 
-fn run_hasher_variant<H>(options: &Options, h: &mut Handler)
+/// CSV header line of the records this version writes.
+fn columns() -> String {
+    let mut wr = csv::Writer::from_writer(Vec::new());
+    wr.serialize(Record::with_defaults())
+        .expect("cannot serialize");
+    let data = wr.into_inner().expect("cannot serialize");
+    let data = String::from_utf8(data).expect("CSV is not UTF-8");
+    data.lines().next().unwrap_or_default().to_string()
+}
+
+/// Checks that records can be appended to the CSV file at `path`: it is
+/// empty, missing, or starts with the same columns this version writes.
+pub(crate) fn check_columns(path: &Path) -> Result<(), String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(format!("cannot read {}: {}", path.display(), e)),
+    };
+
+    match text.lines().next() {
+        Some(header) if header != columns() => Err(format!(
+            "{} holds records with other columns than this version writes, \
+             append to a new file",
+            path.display()
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Appends every `Record` as CSV to the file at `path`, writing the headers
+/// only into an empty file.
+pub(crate) fn append_handler(path: &Path) -> Handler {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .expect("cannot open result file");
+    let empty = file.metadata().expect("cannot stat result file").len() == 0;
+
+    let wr = csv::WriterBuilder::new()
+        .has_headers(empty)
+        .from_writer(file);
+    csv_handler(wr)
+}
+
+/// Serializes every `Record` as CSV into `wr`.
+pub(crate) fn csv_handler<W>(mut wr: csv::Writer<W>) -> Handler
 where
-    H: Default + Clone + Send + Sync + BuildHasher + 'static,
+    W: io::Write + 'static,
 {
-    //case::<StdRwLockStdHashMapTable<u64, H>>("std::sync::RwLock<StdHashMap>", options, h);
-    //case::<ParkingLotRwLockStdHashMapTable<u64, H>>("parking_lot::RwLock<StdHashMap>", options, h);
-    case::<DashMapTable<u64, H>>("DashMap", options, h);
-    case::<FlurryTable<u64, H>>("Flurry", options, h);
-    case::<EvmapTable<u64, H>>("Evmap", options, h);
-    case::<ContrieTable<u64, H>>("Contrie", options, h);
-    case::<SccMapTable<u64, H>>("SccMap", options, h);
+    Box::new(move |record: &Record| {
+        wr.serialize(record).expect("cannot serialize");
+        wr.flush().expect("cannot flush");
+    })
 }
 
-pub fn bench(options: &Options) {
-    println!("== {:?}", options.workload);
-
-    let mut handler = if options.csv {
-        let mut wr = csv::WriterBuilder::new()
-            .has_headers(!options.csv_no_headers)
-            .from_writer(io::stderr());
-
-        Box::new(move |name: &str, n, m: &Measurement| {
-            wr.serialize(Record {
-                name: name.into(),
-                total_ops: m.total_ops,
-                threads: n,
-                spent: m.spent,
-                throughput: m.throughput,
-                latency: m.latency,
-            })
-            .expect("cannot serialize");
-            wr.flush().expect("cannot flush");
-        }) as Handler
+/// Per operation kind: name, operations, success rate, throughput, mean and
+/// p99 latency.
+fn breakdown(r: &Record) -> [(Operation, u64, f64, f64, Duration, Duration); 5] {
+    [
+        (
+            Operation::Read,
+            r.read_count,
+            r.read_success_rate,
+            r.read_throughput,
+            r.read_mean,
+            r.read_p99,
+        ),
+        (
+            Operation::Insert,
+            r.insert_count,
+            r.insert_success_rate,
+            r.insert_throughput,
+            r.insert_mean,
+            r.insert_p99,
+        ),
+        (
+            Operation::Remove,
+            r.remove_count,
+            r.remove_success_rate,
+            r.remove_throughput,
+            r.remove_mean,
+            r.remove_p99,
+        ),
+        (
+            Operation::Update,
+            r.update_count,
+            r.update_success_rate,
+            r.update_throughput,
+            r.update_mean,
+            r.update_p99,
+        ),
+        (
+            Operation::Upsert,
+            r.upsert_count,
+            r.upsert_success_rate,
+            r.upsert_throughput,
+            r.upsert_mean,
+            r.upsert_p99,
+        ),
+    ]
+}
+
+fn write_text(wr: &mut impl io::Write, r: &Record) -> io::Result<()> {
+    if !r.phase.is_empty() {
+        write!(wr, "phase={}\t", r.phase)?;
+    }
+
+    if r.status != "ok" {
+        writeln!(wr, "threads={}\tstatus={}", r.threads, r.status)?;
+        return Ok(());
+    }
+
+    if r.repetitions > 1 {
+        writeln!(
+            wr,
+            "total_ops={}\tthreads={}\tspent={:.1?}\tlatency={:?}±{:?}\tthroughput={:.0}±{:.0}op/s\t\
+             runs={}\tmedian={:.0}op/s\tstddev={:.0}op/s\tmin={:.0}op/s\tmax={:.0}op/s",
+            r.total_ops,
+            r.threads,
+            r.spent,
+            r.latency,
+            r.latency_ci95,
+            r.throughput,
+            r.throughput_ci95,
+            r.repetitions,
+            r.throughput_median,
+            r.throughput_stddev,
+            r.throughput_min,
+            r.throughput_max,
+        )?;
     } else {
-        Box::new(|_: &str, n, m: &Measurement| {
-            eprintln!(
-                "total_ops={}\tthreads={}\tspent={:.1?}\tlatency={:?}\tthroughput={:.0}op/s",
-                m.total_ops, n, m.spent, m.latency, m.throughput,
-            );
-        }) as Handler
+        writeln!(
+            wr,
+            "total_ops={}\tthreads={}\tspent={:.1?}\tlatency={:?}\tthroughput={:.0}op/s",
+            r.total_ops, r.threads, r.spent, r.latency, r.throughput,
+        )?;
+    }
+
+    if !r.all_max.is_zero() {
+        writeln!(
+            wr,
+            "p50={:?}\tp90={:?}\tp99={:?}\tp99.9={:?}\tmax={:?}",
+            r.all_p50, r.all_p90, r.all_p99, r.all_p999, r.all_max,
+        )?;
+    }
+
+    for (op, count, success_rate, throughput, mean, p99) in breakdown(r) {
+        if count > 0 {
+            writeln!(
+                wr,
+                "  {}\tops={}\tsuccess={:.1}%\tthroughput={:.0}op/s\tmean={:?}\tp99={:?}",
+                op.name(),
+                count,
+                success_rate * 100.0,
+                throughput,
+                mean,
+                p99,
+            )?;
+        }
+    }
+
+    if r.rss_end > 0 {
+        let mib = |bytes: u64| bytes as f64 / (1024.0 * 1024.0);
+        writeln!(
+            wr,
+            "  memory\tbefore={:.1}MiB\tprefilled={:.1}MiB\tpeak={:.1}MiB\tend={:.1}MiB\t\
+             dropped={:.1}MiB\tentries={}\tbytes/entry={:.1}",
+            mib(r.rss_before),
+            mib(r.rss_prefilled),
+            mib(r.rss_peak),
+            mib(r.rss_end),
+            mib(r.rss_dropped),
+            r.live_entries,
+            r.bytes_per_entry,
+        )?;
+    }
+
+    if r.threads > 1 && r.fairness > 0.0 {
+        write!(wr, "  fairness")?;
+        if r.hot_keys > 0 {
+            write!(wr, "\thot_keys={}", r.hot_keys)?;
+        }
+        writeln!(
+            wr,
+            "\tjain={:.3}\tslowest={:.0}op/s\tfastest={:.0}op/s",
+            r.fairness, r.thread_throughput_min, r.thread_throughput_max,
+        )?;
+    }
+
+    if allocator::ENABLED {
+        writeln!(
+            wr,
+            "  allocs\tallocs/op={:.3}\tdeallocs/op={:.3}\tbytes/op={:.1}",
+            r.allocations_per_op, r.deallocations_per_op, r.allocated_bytes_per_op,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Writes every `Record` as text into `wr`.
+fn text_handler<W>(mut wr: W) -> Handler
+where
+    W: io::Write + 'static,
+{
+    Box::new(move |record: &Record| {
+        write_text(&mut wr, record).expect("cannot write");
+        wr.flush().expect("cannot flush");
+    })
+}
+
+/// Writes every `Record` in `--format` to the `--output` file, with its
+/// metadata next to it, or to stderr.
+fn output_handler(options: &Options) -> Handler {
+    let file = options.output.as_ref().map(|path| {
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(options.append)
+            .truncate(!options.append)
+            .open(path)
+            .unwrap_or_else(|e| {
+                let message = format!("cannot open {}: {}", path.display(), e);
+                clap::Error::with_description(&message, clap::ErrorKind::Io).exit()
+            });
+        metadata::write(path, options, options.append);
+        file
+    });
+    let empty = match &file {
+        Some(file) => file.metadata().expect("cannot stat output file").len() == 0,
+        None => true,
+    };
+
+    // A json document can only be kept complete in a file, a stream gets the
+    // records one per line.
+    let wr: Box<dyn io::Write> = match (options.format, file) {
+        (Format::Json, Some(file)) => {
+            return format::json_handler(file, metadata::collect(options))
+        }
+        (_, Some(file)) => Box::new(file),
+        (_, None) => Box::new(io::stderr()),
     };
 
-    run(options, &mut handler);
+    match options.format {
+        Format::Text => text_handler(wr),
+        Format::Csv => csv_handler(csv::WriterBuilder::new().has_headers(empty).from_writer(wr)),
+        Format::Json | Format::Jsonl => format::jsonl_handler(wr, metadata::collect(options)),
+    }
+}
+
+/// Checks that the options can be run together, for `bench` and every run
+/// of a suite alike.
+pub(crate) fn validate(options: &Options) -> Result<(), String> {
+    if options.append && options.format == Format::Json {
+        return Err("--append can't add to a json document, use --format jsonl".into());
+    }
+
+    if let (true, Format::Csv, Some(path)) = (options.append, options.format, &options.output) {
+        check_columns(path)?;
+    }
+
+    if let Some(dir) = &options.resume {
+        check_columns(&dir.join("results.csv"))?;
+    }
+
+    if options.in_process && options.timeout_secs > 0 {
+        return Err(
+            "--timeout-secs can't be used with --in-process, the threads of a timed \
+                    out run can't be stopped and would distort the runs after it"
+                .into(),
+        );
+    }
+
+    workloads::validate(options)
+}
+
+pub fn bench(options: &Options) {
+    if let Err(message) = validate(options) {
+        clap::Error::with_description(&message, clap::ErrorKind::ValueValidation).exit();
+    }
+
+    let mut state = options.resume.as_deref().map(RunState::open);
+
+    if let Some(path) = &options.child {
+        let file = OpenOptions::new()
+            .append(true)
+            .open(path)
+            .expect("cannot open records file");
+        run(
+            options,
+            &mut csv_handler(csv::Writer::from_writer(file)),
+            state.as_mut(),
+        );
+        return;
+    }
+
+    let mut handler = output_handler(options);
+
+    if let Some(dir) = &options.resume {
+        fs::create_dir_all(dir).expect("cannot create resume directory");
+        let path = dir.join("results.csv");
+        metadata::write(&path, options, true);
+        let mut results = append_handler(&path);
+        let mut display = handler;
+        handler = Box::new(move |record: &Record| {
+            results(record);
+            display(record);
+        });
+    }
+
+    run(options, &mut handler, state.as_mut());
 }
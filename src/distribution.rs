@@ -0,0 +1,217 @@
+use std::{fmt, str::FromStr};
+
+/// How reads, updates and upserts pick the key they operate on.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum KeyDistribution {
+    /// Every key a thread inserts is equally likely, each thread only looks
+    /// up its own keys.
+    #[default]
+    Uniform,
+    /// Zipf distribution with exponent `theta` over the keys of all threads.
+    Zipf(f64),
+    /// `ops` percent of the operations go to `keys` percent of the keys of
+    /// all threads, the rest is spread uniformly over the remaining keys.
+    HotSet { ops: f64, keys: f64 },
+    /// Each thread cycles through its present keys in insertion order.
+    Sequential,
+    /// Zipf distribution with exponent `theta` over a thread's own keys,
+    /// ranked from the most recently inserted one.
+    Latest(f64),
+    /// All threads share the first `keys` keys, inserted before the run.
+    Hot(u64),
+}
+
+/// Exponent used by YCSB when none is given.
+const DEFAULT_THETA: f64 = 0.99;
+
+impl KeyDistribution {
+    /// Whether lookups may hit keys owned by other threads, whose presence is
+    /// not known to the looking-up thread.
+    pub fn shared(self) -> bool {
+        matches!(
+            self,
+            KeyDistribution::Zipf(_) | KeyDistribution::HotSet { .. } | KeyDistribution::Hot(_)
+        )
+    }
+}
+
+impl fmt::Display for KeyDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyDistribution::Uniform => write!(f, "uniform"),
+            KeyDistribution::Zipf(theta) => write!(f, "zipf:{}", theta),
+            KeyDistribution::HotSet { ops, keys } => write!(f, "hotset:{}/{}", ops, keys),
+            KeyDistribution::Sequential => write!(f, "sequential"),
+            KeyDistribution::Latest(theta) => write!(f, "latest:{}", theta),
+            KeyDistribution::Hot(keys) => write!(f, "hot:{}", keys),
+        }
+    }
+}
+
+fn parse_theta(param: Option<&str>) -> Result<f64, String> {
+    let theta = match param {
+        Some(theta) => theta
+            .parse()
+            .map_err(|_| format!("invalid zipf exponent '{}'", theta))?,
+        None => DEFAULT_THETA,
+    };
+
+    if theta > 0.0 && theta.is_finite() {
+        Ok(theta)
+    } else {
+        Err("zipf exponent must be positive".into())
+    }
+}
+
+fn parse_percent(percent: &str) -> Result<f64, String> {
+    match percent.parse::<f64>() {
+        Ok(p) if p > 0.0 && p < 100.0 => Ok(p),
+        _ => Err(format!(
+            "hot set percentage '{}' must be between 0 and 100",
+            percent
+        )),
+    }
+}
+
+impl FromStr for KeyDistribution {
+    type Err = String;
+
+    /// Parses `uniform`, `zipf[:theta]`, `hotset[:ops/keys]`, `sequential`,
+    /// `latest[:theta]` and `hot[:keys]`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, param) = match s.split_once(':') {
+            Some((name, param)) => (name, Some(param)),
+            None => (s, None),
+        };
+
+        match (name, param) {
+            ("uniform", None) => Ok(KeyDistribution::Uniform),
+            ("zipf", param) => parse_theta(param).map(KeyDistribution::Zipf),
+            ("hotset", None) => Ok(KeyDistribution::HotSet {
+                ops: 90.0,
+                keys: 10.0,
+            }),
+            ("hotset", Some(param)) => {
+                let (ops, keys) = param
+                    .split_once('/')
+                    .ok_or("hot set must be given as 'hotset:<ops>/<keys>'")?;
+                Ok(KeyDistribution::HotSet {
+                    ops: parse_percent(ops)?,
+                    keys: parse_percent(keys)?,
+                })
+            }
+            ("sequential", None) => Ok(KeyDistribution::Sequential),
+            ("latest", param) => parse_theta(param).map(KeyDistribution::Latest),
+            ("hot", None) => Ok(KeyDistribution::Hot(1)),
+            ("hot", Some(keys)) => match keys.parse() {
+                Ok(keys) if keys > 0 => Ok(KeyDistribution::Hot(keys)),
+                _ => Err(format!("invalid number of hot keys '{}'", keys)),
+            },
+            _ => Err(format!(
+                "unknown key distribution '{}', must be one of uniform, zipf[:theta], \
+                 hotset[:ops/keys], sequential, latest[:theta] or hot[:keys]",
+                s
+            )),
+        }
+    }
+}
+
+/// xorshift64, cheap enough to not show up in the measurement.
+#[derive(Debug, Clone)]
+pub struct XorShift(u64);
+
+impl XorShift {
+    pub fn new(seed: u64) -> Self {
+        Self(seed | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// Uniform in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform in `[0, n)`, `n` must not be zero.
+    pub fn below(&mut self, n: u64) -> u64 {
+        ((u128::from(self.next_u64()) * u128::from(n)) >> 64) as u64
+    }
+}
+
+/// `ln(1 + x) / x`, accurate near zero.
+fn helper1(x: f64) -> f64 {
+    if x.abs() > 1e-8 {
+        x.ln_1p() / x
+    } else {
+        1.0 - x * (0.5 - x * (1.0 / 3.0 - 0.25 * x))
+    }
+}
+
+/// `(exp(x) - 1) / x`, accurate near zero.
+fn helper2(x: f64) -> f64 {
+    if x.abs() > 1e-8 {
+        x.exp_m1() / x
+    } else {
+        1.0 + x * 0.5 * (1.0 + x * (1.0 / 3.0) * (1.0 + 0.25 * x))
+    }
+}
+
+/// Zipf sampler over `0..n` using rejection-inversion (Hörmann and Derflinger),
+/// which needs constant setup unlike the classic YCSB generator that sums
+/// `n` terms up front.
+#[derive(Debug, Clone)]
+pub struct Zipf {
+    n: f64,
+    theta: f64,
+    h_integral_x1: f64,
+    h_integral_n: f64,
+    s: f64,
+}
+
+impl Zipf {
+    pub fn new(n: u64, theta: f64) -> Self {
+        let mut zipf = Self {
+            n: n.max(1) as f64,
+            theta,
+            h_integral_x1: 0.0,
+            h_integral_n: 0.0,
+            s: 0.0,
+        };
+        zipf.h_integral_x1 = zipf.h_integral(1.5) - 1.0;
+        zipf.h_integral_n = zipf.h_integral(zipf.n + 0.5);
+        zipf.s = 2.0 - zipf.h_integral_inverse(zipf.h_integral(2.5) - zipf.h(2.0));
+        zipf
+    }
+
+    fn h(&self, x: f64) -> f64 {
+        (-self.theta * x.ln()).exp()
+    }
+
+    fn h_integral(&self, x: f64) -> f64 {
+        let log_x = x.ln();
+        helper2((1.0 - self.theta) * log_x) * log_x
+    }
+
+    fn h_integral_inverse(&self, x: f64) -> f64 {
+        let t = (x * (1.0 - self.theta)).max(-1.0);
+        (helper1(t) * x).exp()
+    }
+
+    /// Rank of the next key, 0 being the most popular one.
+    pub fn sample(&self, rng: &mut XorShift) -> u64 {
+        loop {
+            let u = self.h_integral_n + rng.next_f64() * (self.h_integral_x1 - self.h_integral_n);
+            let x = self.h_integral_inverse(u);
+            let k = (x + 0.5).floor().clamp(1.0, self.n);
+
+            if k - x <= self.s || u >= self.h_integral(k + 0.5) - self.h(k) {
+                return k as u64 - 1;
+            }
+        }
+    }
+}
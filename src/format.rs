@@ -0,0 +1,405 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    str::FromStr,
+    time::Duration,
+};
+
+use json::JsonValue;
+
+use crate::{bench::Handler, record::Record};
+
+/// How records are written and read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Human readable lines, write only.
+    Text,
+    Csv,
+    /// One document holding the metadata and an array of records, written
+    /// as `Jsonl` when streamed.
+    Json,
+    /// The metadata and then one record per line.
+    Jsonl,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Format::Text => write!(f, "text"),
+            Format::Csv => write!(f, "csv"),
+            Format::Json => write!(f, "json"),
+            Format::Jsonl => write!(f, "jsonl"),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Format::Text),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "jsonl" => Ok(Format::Jsonl),
+            _ => Err("invalid format, must be one of 'text', 'csv', 'json' or 'jsonl'"),
+        }
+    }
+}
+
+/// Reads and writes one field of a record.
+enum Field {
+    Text(fn(&Record) -> &String, fn(&mut Record) -> &mut String),
+    /// Space separated CPU list, an array in JSON.
+    Cpus(fn(&Record) -> &String, fn(&mut Record) -> &mut String),
+    U32(fn(&Record) -> &u32, fn(&mut Record) -> &mut u32),
+    U64(fn(&Record) -> &u64, fn(&mut Record) -> &mut u64),
+    F64(fn(&Record) -> &f64, fn(&mut Record) -> &mut f64),
+    /// In nanoseconds.
+    Nanos(fn(&Record) -> &Duration, fn(&mut Record) -> &mut Duration),
+}
+
+/// The `Field` of record field `$name`, holding a `$kind`.
+macro_rules! field {
+    ($kind:ident, $name:ident) => {
+        Field::$kind(|r| &r.$name, |r| &mut r.$name)
+    };
+}
+
+/// p50, p90, p99, p99.9 and max latency of operation kind `$op`.
+macro_rules! percentiles {
+    ($op:literal, $p50:ident, $p90:ident, $p99:ident, $p999:ident, $max:ident) => {
+        vec![
+            (vec!["operations", $op, "p50"], field!(Nanos, $p50)),
+            (vec!["operations", $op, "p90"], field!(Nanos, $p90)),
+            (vec!["operations", $op, "p99"], field!(Nanos, $p99)),
+            (vec!["operations", $op, "p999"], field!(Nanos, $p999)),
+            (vec!["operations", $op, "max"], field!(Nanos, $max)),
+        ]
+    };
+}
+
+/// Operations, success rate, throughput and mean latency of operation kind
+/// `$op`.
+macro_rules! breakdown {
+    ($op:literal, $count:ident, $success_rate:ident, $throughput:ident, $mean:ident) => {
+        vec![
+            (vec!["operations", $op, "count"], field!(U64, $count)),
+            (
+                vec!["operations", $op, "success_rate"],
+                field!(F64, $success_rate),
+            ),
+            (
+                vec!["operations", $op, "throughput"],
+                field!(F64, $throughput),
+            ),
+            (vec!["operations", $op, "mean"], field!(Nanos, $mean)),
+        ]
+    };
+}
+
+/// Fields a JSON record can't do without, all others have defaults.
+const REQUIRED: &[&str] = &[
+    "map",
+    "total_ops",
+    "threads",
+    "spent",
+    "throughput.mean",
+    "latency.mean",
+];
+
+/// Every field of a record with its place in the JSON form: throughput and
+/// latency statistics under `throughput` and `latency`, per operation fields
+/// under `operations`, memory usage under `memory`, allocator activity under
+/// `allocations` and how evenly the threads progressed under `fairness`.
+fn fields() -> Vec<(Vec<&'static str>, Field)> {
+    let mut fields = vec![
+        (vec!["map"], field!(Text, name)),
+        (vec!["total_ops"], field!(U64, total_ops)),
+        (vec!["threads"], field!(U32, threads)),
+        (vec!["spent"], field!(Nanos, spent)),
+        (vec!["workload"], field!(Text, workload)),
+        (vec!["phase"], field!(Text, phase)),
+        (vec!["hasher"], field!(Text, hasher)),
+        (vec!["distribution"], field!(Text, distribution)),
+        (vec!["key_type"], field!(Text, key_type)),
+        (vec!["value_type"], field!(Text, value_type)),
+        (vec!["pin"], field!(Text, pin)),
+        (vec!["cpus"], field!(Cpus, cpus)),
+        (vec!["status"], field!(Text, status)),
+        (vec!["repetitions"], field!(U32, repetitions)),
+        (vec!["throughput", "mean"], field!(F64, throughput)),
+        (vec!["throughput", "median"], field!(F64, throughput_median)),
+        (vec!["throughput", "stddev"], field!(F64, throughput_stddev)),
+        (vec!["throughput", "min"], field!(F64, throughput_min)),
+        (vec!["throughput", "max"], field!(F64, throughput_max)),
+        (vec!["throughput", "ci95"], field!(F64, throughput_ci95)),
+        (vec!["latency", "mean"], field!(Nanos, latency)),
+        (vec!["latency", "median"], field!(Nanos, latency_median)),
+        (vec!["latency", "stddev"], field!(Nanos, latency_stddev)),
+        (vec!["latency", "min"], field!(Nanos, latency_min)),
+        (vec!["latency", "max"], field!(Nanos, latency_max)),
+        (vec!["latency", "ci95"], field!(Nanos, latency_ci95)),
+    ];
+
+    fields.extend(percentiles!(
+        "all", all_p50, all_p90, all_p99, all_p999, all_max
+    ));
+    fields.extend(percentiles!(
+        "read", read_p50, read_p90, read_p99, read_p999, read_max
+    ));
+    fields.extend(breakdown!(
+        "read",
+        read_count,
+        read_success_rate,
+        read_throughput,
+        read_mean
+    ));
+    fields.extend(percentiles!(
+        "insert",
+        insert_p50,
+        insert_p90,
+        insert_p99,
+        insert_p999,
+        insert_max
+    ));
+    fields.extend(breakdown!(
+        "insert",
+        insert_count,
+        insert_success_rate,
+        insert_throughput,
+        insert_mean
+    ));
+    fields.extend(percentiles!(
+        "remove",
+        remove_p50,
+        remove_p90,
+        remove_p99,
+        remove_p999,
+        remove_max
+    ));
+    fields.extend(breakdown!(
+        "remove",
+        remove_count,
+        remove_success_rate,
+        remove_throughput,
+        remove_mean
+    ));
+    fields.extend(percentiles!(
+        "update",
+        update_p50,
+        update_p90,
+        update_p99,
+        update_p999,
+        update_max
+    ));
+    fields.extend(breakdown!(
+        "update",
+        update_count,
+        update_success_rate,
+        update_throughput,
+        update_mean
+    ));
+    fields.extend(percentiles!(
+        "upsert",
+        upsert_p50,
+        upsert_p90,
+        upsert_p99,
+        upsert_p999,
+        upsert_max
+    ));
+    fields.extend(breakdown!(
+        "upsert",
+        upsert_count,
+        upsert_success_rate,
+        upsert_throughput,
+        upsert_mean
+    ));
+
+    fields.extend(vec![
+        (vec!["memory", "before"], field!(U64, rss_before)),
+        (vec!["memory", "prefilled"], field!(U64, rss_prefilled)),
+        (vec!["memory", "peak"], field!(U64, rss_peak)),
+        (vec!["memory", "end"], field!(U64, rss_end)),
+        (vec!["memory", "dropped"], field!(U64, rss_dropped)),
+        (vec!["memory", "live_entries"], field!(U64, live_entries)),
+        (
+            vec!["memory", "bytes_per_entry"],
+            field!(F64, bytes_per_entry),
+        ),
+        (
+            vec!["allocations", "allocations_per_op"],
+            field!(F64, allocations_per_op),
+        ),
+        (
+            vec!["allocations", "deallocations_per_op"],
+            field!(F64, deallocations_per_op),
+        ),
+        (
+            vec!["allocations", "allocated_bytes_per_op"],
+            field!(F64, allocated_bytes_per_op),
+        ),
+        (vec!["fairness", "jain"], field!(F64, fairness)),
+        (
+            vec!["fairness", "throughput_min"],
+            field!(F64, thread_throughput_min),
+        ),
+        (
+            vec!["fairness", "throughput_max"],
+            field!(F64, thread_throughput_max),
+        ),
+        (vec!["fairness", "hot_keys"], field!(U64, hot_keys)),
+    ]);
+
+    fields
+}
+
+/// Structured JSON form of `record`.
+pub fn to_json(record: &Record) -> JsonValue {
+    let mut root = JsonValue::new_object();
+
+    for (path, field) in fields() {
+        let (key, parents) = path.split_last().expect("empty path");
+
+        let mut node = &mut root;
+        for parent in parents {
+            if !node.has_key(parent) {
+                node[*parent] = JsonValue::new_object();
+            }
+            node = &mut node[*parent];
+        }
+
+        node[*key] = match field {
+            Field::Text(get, _) => get(record).as_str().into(),
+            Field::Cpus(get, _) => get(record)
+                .split_whitespace()
+                .filter_map(|cpu| cpu.parse::<u64>().ok())
+                .collect::<Vec<_>>()
+                .into(),
+            Field::U32(get, _) => (*get(record)).into(),
+            Field::U64(get, _) => (*get(record)).into(),
+            Field::F64(get, _) => (*get(record)).into(),
+            Field::Nanos(get, _) => (get(record).as_nanos() as u64).into(),
+        };
+    }
+
+    root
+}
+
+/// Reads a record back from its JSON form.
+pub fn from_json(value: &JsonValue) -> Result<Record, String> {
+    if !value.is_object() {
+        return Err("record must be a JSON object".into());
+    }
+
+    let mut record = Record::with_defaults();
+    for (path, field) in fields() {
+        let node = path.iter().fold(value, |node, key| &node[*key]);
+        let name = path.join(".");
+
+        if node.is_null() {
+            if REQUIRED.contains(&name.as_str()) {
+                return Err(format!("record without '{}'", name));
+            }
+            continue;
+        }
+
+        let invalid = || format!("invalid value for '{}'", name);
+        match field {
+            Field::Text(_, set) => *set(&mut record) = node.as_str().ok_or_else(invalid)?.into(),
+            Field::Cpus(_, set) => {
+                let list: Option<Vec<_>> = node
+                    .members()
+                    .map(|cpu| cpu.as_u64().map(|cpu| cpu.to_string()))
+                    .collect();
+                match list {
+                    Some(list) if node.is_array() => *set(&mut record) = list.join(" "),
+                    _ => return Err(invalid()),
+                }
+            }
+            Field::U32(_, set) => *set(&mut record) = node.as_u32().ok_or_else(invalid)?,
+            Field::U64(_, set) => *set(&mut record) = node.as_u64().ok_or_else(invalid)?,
+            Field::F64(_, set) => *set(&mut record) = node.as_f64().ok_or_else(invalid)?,
+            Field::Nanos(_, set) => {
+                *set(&mut record) = Duration::from_nanos(node.as_u64().ok_or_else(invalid)?)
+            }
+        }
+    }
+
+    Ok(record)
+}
+
+/// Closes the records array and the document.
+const CLOSING: &str = "\n]}\n";
+
+/// Writes records as a `Json` document into `file`, starting with
+/// `metadata`. The document is closed after every record and reopened by the
+/// next one, so that it is complete however the run ends.
+pub fn json_handler(mut file: File, metadata: JsonValue) -> Handler {
+    write!(
+        file,
+        "{{\"metadata\":{},\"records\":[{}",
+        metadata.dump(),
+        CLOSING
+    )
+    .expect("cannot write");
+    let mut records = 0;
+
+    Box::new(move |record: &Record| {
+        let separator = if records == 0 { "" } else { "," };
+        file.seek(SeekFrom::End(-(CLOSING.len() as i64)))
+            .expect("cannot seek");
+        write!(file, "{}\n{}{}", separator, to_json(record).dump(), CLOSING).expect("cannot write");
+        file.flush().expect("cannot flush");
+        records += 1;
+    })
+}
+
+/// Writes records as `Jsonl` into `wr`, starting with `metadata`.
+pub fn jsonl_handler<W>(mut wr: W, metadata: JsonValue) -> Handler
+where
+    W: Write + 'static,
+{
+    let mut header = JsonValue::new_object();
+    header["metadata"] = metadata;
+    writeln!(wr, "{}", header.dump()).expect("cannot write");
+
+    Box::new(move |record: &Record| {
+        writeln!(wr, "{}", to_json(record).dump()).expect("cannot write");
+        wr.flush().expect("cannot flush");
+    })
+}
+
+/// Reads the records of a `Csv`, `Json` or `Jsonl` stream.
+pub fn read<R: Read>(format: Format, rd: R) -> Result<Vec<Record>, String> {
+    match format {
+        Format::Text => Err("text output can't be read back, use csv, json or jsonl".into()),
+        Format::Csv => csv::Reader::from_reader(rd)
+            .deserialize()
+            .map(|record| record.map_err(|e| format!("invalid record: {}", e)))
+            .collect(),
+        Format::Json => {
+            let mut text = String::new();
+            BufReader::new(rd)
+                .read_to_string(&mut text)
+                .map_err(|e| e.to_string())?;
+            let root = json::parse(&text).map_err(|e| e.to_string())?;
+            root["records"].members().map(from_json).collect()
+        }
+        Format::Jsonl => {
+            let mut records = Vec::new();
+            for line in BufReader::new(rd).lines() {
+                let line = line.map_err(|e: io::Error| e.to_string())?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let value = json::parse(&line).map_err(|e| e.to_string())?;
+                if !value.has_key("metadata") {
+                    records.push(from_json(&value)?);
+                }
+            }
+            Ok(records)
+        }
+    }
+}
@@ -0,0 +1,1079 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use bustle::{Collection, Mix};
+use json::JsonValue;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    adapters::Upsert,
+    affinity,
+    allocator::{self, AllocCount},
+    distribution::{KeyDistribution, XorShift, Zipf},
+    histogram::Histogram,
+    keys::mix64,
+    memory,
+    phases::{Length, Phase},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Read,
+    Insert,
+    Remove,
+    Update,
+    Upsert,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 5] = [
+        Operation::Read,
+        Operation::Insert,
+        Operation::Remove,
+        Operation::Update,
+        Operation::Upsert,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Operation::Read => "read",
+            Operation::Insert => "insert",
+            Operation::Remove => "remove",
+            Operation::Update => "update",
+            Operation::Upsert => "upsert",
+        }
+    }
+}
+
+/// How often an operation ran and how often the map reported success.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OperationCount {
+    pub total: u64,
+    pub succeeded: u64,
+}
+
+impl OperationCount {
+    pub fn add(&mut self, other: &OperationCount) {
+        self.total += other.total;
+        self.succeeded += other.succeeded;
+    }
+}
+
+/// Parameters of a single run, built like `bustle::Workload`.
+#[derive(Debug, Clone)]
+pub struct Workload {
+    threads: usize,
+    mix: Mix,
+    initial_capacity_log2: u8,
+    prefill_fraction: f64,
+    operations: f64,
+    duration: Option<Duration>,
+    warmup: Option<Length>,
+    latency_sample: u32,
+    distribution: KeyDistribution,
+    timeout: Option<Duration>,
+    cpus: Option<Vec<usize>>,
+    phases: Vec<Phase>,
+}
+
+impl Workload {
+    pub fn new(threads: usize, mix: Mix) -> Self {
+        Self {
+            threads,
+            mix,
+            initial_capacity_log2: 25,
+            prefill_fraction: 0.0,
+            operations: 1.0,
+            duration: None,
+            warmup: None,
+            latency_sample: 1,
+            distribution: KeyDistribution::Uniform,
+            timeout: None,
+            cpus: None,
+            phases: Vec::new(),
+        }
+    }
+
+    pub fn initial_capacity_log2(&mut self, log2: u8) -> &mut Self {
+        self.initial_capacity_log2 = log2;
+        self
+    }
+
+    pub fn prefill_fraction(&mut self, fraction: f64) -> &mut Self {
+        self.prefill_fraction = fraction;
+        self
+    }
+
+    /// Number of operations as a multiple of the initial capacity.
+    pub fn operations(&mut self, multiplier: f64) -> &mut Self {
+        self.operations = multiplier;
+        self
+    }
+
+    /// Let every thread run the mix until `duration` has passed instead of
+    /// for the number of operations.
+    pub fn duration(&mut self, duration: Duration) -> &mut Self {
+        self.duration = Some(duration);
+        self
+    }
+
+    /// Run the mix for `warmup` after prefilling before the measured phases,
+    /// as a phase of its own named `WARMUP`.
+    pub fn warmup(&mut self, warmup: Length) -> &mut Self {
+        self.warmup = Some(warmup);
+        self
+    }
+
+    /// Time every `n`-th operation into the latency histograms, 0 disables
+    /// timing of individual operations.
+    pub fn latency_sample(&mut self, n: u32) -> &mut Self {
+        self.latency_sample = n;
+        self
+    }
+
+    /// How reads, updates and upserts pick their keys.
+    pub fn distribution(&mut self, distribution: KeyDistribution) -> &mut Self {
+        self.distribution = distribution;
+        self
+    }
+
+    /// Give up on the run if it takes longer than `timeout`, prefill included.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Pin the `i`-th worker thread to `cpus[i]`.
+    pub fn cpus(&mut self, cpus: Vec<usize>) -> &mut Self {
+        self.cpus = Some(cpus);
+        self
+    }
+
+    /// Run these phases one after the other on the same map instead of the
+    /// mix for the number of operations.
+    pub fn phases(&mut self, phases: Vec<Phase>) -> &mut Self {
+        self.phases = phases;
+        self
+    }
+
+    /// The phases of the run, a single unnamed one unless `phases` were set,
+    /// after the warm-up if there is one.
+    fn steps(&self) -> Vec<Phase> {
+        let mut steps = Vec::new();
+        if let Some(warmup) = self.warmup {
+            steps.push(Phase {
+                name: WARMUP.into(),
+                mix: self.phases.first().map_or(self.mix, |phase| phase.mix),
+                length: warmup,
+            });
+        }
+
+        if self.phases.is_empty() {
+            let length = match self.duration {
+                Some(duration) => Length::Time(duration),
+                None => Length::Operations(self.operations),
+            };
+            steps.push(Phase {
+                name: String::new(),
+                mix: self.mix,
+                length,
+            });
+        } else {
+            steps.extend(self.phases.iter().cloned());
+        }
+        steps
+    }
+
+    fn capacity(&self) -> u64 {
+        1 << self.initial_capacity_log2
+    }
+
+    /// Keys every thread inserts before the first phase.
+    fn prefill(&self) -> u64 {
+        let threads = self.threads.max(1) as u64;
+        let prefill = (self.capacity() as f64 * self.prefill_fraction) as u64 / threads;
+        match self.distribution {
+            KeyDistribution::Hot(keys) => prefill.max((keys - 1) / threads + 1),
+            _ => prefill,
+        }
+    }
+
+    /// Operations every thread runs in a phase of `length`, 0 if it runs for
+    /// a time.
+    fn ops(&self, length: Length) -> u64 {
+        match length {
+            Length::Operations(multiplier) => {
+                (self.capacity() as f64 * multiplier) as u64 / self.threads.max(1) as u64
+            }
+            Length::Time(_) => 0,
+        }
+    }
+
+    /// Mask of the key indices every thread looks up. Phases that run for a
+    /// time are assumed to insert up to the capacity, lookups of keys beyond
+    /// that go to the ones inserted before.
+    fn find_mask(&self, steps: &[Phase]) -> u64 {
+        let max_inserts = steps
+            .iter()
+            .map(|phase| {
+                let inserts = u64::from(phase.mix.insert + phase.mix.upsert);
+                match phase.length {
+                    Length::Time(_) if inserts > 0 => self.capacity() / self.threads.max(1) as u64,
+                    length => self.ops(length) * inserts / 100,
+                }
+            })
+            .sum::<u64>()
+            + 100;
+        (self.prefill() + max_inserts).next_power_of_two() - 1
+    }
+
+    /// Number of distinct key ids the run hands out over all threads, `None`
+    /// if a phase inserts for a time and so without bound.
+    pub fn key_ids(&self) -> Option<u64> {
+        let steps = self.steps();
+        let unbounded = steps.iter().any(|phase| {
+            matches!(phase.length, Length::Time(_)) && phase.mix.insert + phase.mix.upsert > 0
+        });
+        if unbounded {
+            return None;
+        }
+
+        // Every index a thread inserts, removes or looks up is within the
+        // mask.
+        Some((self.find_mask(&steps) + 1) * self.threads.max(1) as u64)
+    }
+
+    /// Whether any phase of the run removes keys.
+    pub fn removes(&self) -> bool {
+        self.steps().iter().any(|phase| phase.mix.remove > 0)
+    }
+}
+
+/// Name of the phase a run starts with when it warms up, which measures the
+/// map while it is still cold.
+pub const WARMUP: &str = "warmup";
+
+/// Resident set size around a run in bytes, zero where it can't be read.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Memory {
+    /// Before the map is created.
+    pub before: u64,
+    /// After all threads finished prefilling.
+    pub prefilled: u64,
+    pub peak: u64,
+    /// After the last operation, with the map still alive.
+    pub end: u64,
+    /// After the map is dropped.
+    pub dropped: u64,
+    /// Entries in the map at the end of the run.
+    pub live_entries: u64,
+}
+
+/// One phase of a run, the whole run unless it has phases.
+#[derive(Debug)]
+pub struct Measurement {
+    /// Name of the phase, empty without phases.
+    pub phase: String,
+    pub total_ops: u64,
+    pub spent: Duration,
+    pub throughput: f64,
+    pub latency: Duration,
+    /// Sampled operation latencies in nanoseconds, indexed by `Operation`.
+    pub latencies: [Histogram; 5],
+    /// Executed operations, indexed by `Operation`.
+    pub counts: [OperationCount; 5],
+    pub memory: Memory,
+    /// Allocator activity of all workers while measuring, zero unless
+    /// `allocator::ENABLED`.
+    pub allocations: AllocCount,
+    /// Operations per second of every thread, in thread order.
+    pub thread_throughput: Vec<f64>,
+}
+
+impl Measurement {
+    /// The measurement as JSON, to keep it until the other repetitions of its
+    /// point are measured.
+    pub fn to_json(&self) -> JsonValue {
+        let memory = &self.memory;
+        let allocations = &self.allocations;
+
+        let mut value = JsonValue::new_object();
+        value["phase"] = self.phase.as_str().into();
+        value["total_ops"] = self.total_ops.into();
+        value["spent"] = (self.spent.as_nanos() as u64).into();
+        value["throughput"] = self.throughput.into();
+        value["latency"] = (self.latency.as_nanos() as u64).into();
+        value["latencies"] = self
+            .latencies
+            .iter()
+            .map(Histogram::to_json)
+            .collect::<Vec<_>>()
+            .into();
+        value["counts"] = self
+            .counts
+            .iter()
+            .map(|count| vec![count.total, count.succeeded])
+            .collect::<Vec<_>>()
+            .into();
+        value["memory"] = vec![
+            memory.before,
+            memory.prefilled,
+            memory.peak,
+            memory.end,
+            memory.dropped,
+            memory.live_entries,
+        ]
+        .into();
+        value["allocations"] = vec![
+            allocations.allocations,
+            allocations.deallocations,
+            allocations.bytes,
+        ]
+        .into();
+        value["thread_throughput"] = self.thread_throughput.clone().into();
+        value
+    }
+
+    /// Reads back a measurement written by `to_json`.
+    pub fn from_json(value: &JsonValue) -> Option<Measurement> {
+        fn numbers<T>(value: &JsonValue, get: fn(&JsonValue) -> Option<T>) -> Option<Vec<T>> {
+            value.members().map(get).collect()
+        }
+
+        let mut latencies: [Histogram; 5] = Default::default();
+        if value["latencies"].len() != latencies.len() {
+            return None;
+        }
+        for (histogram, value) in latencies.iter_mut().zip(value["latencies"].members()) {
+            *histogram = Histogram::from_json(value)?;
+        }
+
+        let mut counts = [OperationCount::default(); 5];
+        if value["counts"].len() != counts.len() {
+            return None;
+        }
+        for (count, value) in counts.iter_mut().zip(value["counts"].members()) {
+            match numbers(value, JsonValue::as_u64)?[..] {
+                [total, succeeded] => *count = OperationCount { total, succeeded },
+                _ => return None,
+            }
+        }
+
+        let memory = match numbers(&value["memory"], JsonValue::as_u64)?[..] {
+            [before, prefilled, peak, end, dropped, live_entries] => Memory {
+                before,
+                prefilled,
+                peak,
+                end,
+                dropped,
+                live_entries,
+            },
+            _ => return None,
+        };
+        let allocations = match numbers(&value["allocations"], JsonValue::as_u64)?[..] {
+            [allocations, deallocations, bytes] => AllocCount {
+                allocations,
+                deallocations,
+                bytes,
+            },
+            _ => return None,
+        };
+
+        Some(Measurement {
+            phase: value["phase"].as_str()?.into(),
+            total_ops: value["total_ops"].as_u64()?,
+            spent: Duration::from_nanos(value["spent"].as_u64()?),
+            throughput: value["throughput"].as_f64()?,
+            latency: Duration::from_nanos(value["latency"].as_u64()?),
+            latencies,
+            counts,
+            memory,
+            allocations,
+            thread_throughput: numbers(&value["thread_throughput"], JsonValue::as_f64)?,
+        })
+    }
+}
+
+/// A run that didn't finish in time. Its threads are abandoned, still holding
+/// on to the map.
+#[derive(Debug)]
+pub struct Timeout {
+    pub after: Duration,
+    /// Operations each thread has to execute, prefill included, unknown if a
+    /// phase runs for a time.
+    pub expected: Option<u64>,
+    /// Operations each thread executed.
+    pub progress: Vec<u64>,
+    /// Threads that executed all of their operations.
+    pub finished: Vec<usize>,
+    /// Unfinished threads that executed no operation over `STALL_WINDOW`.
+    pub stalled: Vec<usize>,
+}
+
+/// How long the watchdog observes the threads of a timed out run.
+const STALL_WINDOW: Duration = Duration::from_secs(1);
+
+impl fmt::Display for Timeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "timeout after {:.1?}", self.after)?;
+
+        if self.stalled.is_empty() {
+            return write!(f, ", all threads progressing");
+        }
+
+        let threads: Vec<_> = self.stalled.iter().map(usize::to_string).collect();
+        write!(f, ", no progress on threads {}", threads.join(" "))
+    }
+}
+
+/// Spreads the `rank`-th most popular key of a thread over all of its key
+/// indices, so that popularity is independent of insertion order.
+fn scatter(rank: u64, mask: u64) -> u64 {
+    rank.wrapping_mul(0x9e37_79b9_7f4a_7c15) & mask
+}
+
+/// Per-thread key bookkeeping. Every thread owns a disjoint key sequence of
+/// which `[erased, inserted)` is currently in the map, so unless lookups are
+/// shared between threads the expected result of every operation is known.
+struct Keys {
+    seed: u64,
+    thread: usize,
+    threads: usize,
+    erased: u64,
+    inserted: u64,
+    find: u64,
+    find_mask: u64,
+    distribution: KeyDistribution,
+    zipf: Option<Zipf>,
+    rng: XorShift,
+}
+
+impl Keys {
+    /// The `index`-th key of `thread`. Ids are dense, so they never collide
+    /// between threads or indices and stay distinct in the low bits used by
+    /// narrow key types, see `crate::keys`.
+    fn get<K: From<u64>>(&self, thread: usize, index: u64) -> K {
+        let id = index * self.threads as u64 + thread as u64;
+        self.seed.wrapping_add(id).into()
+    }
+
+    /// Whether results can be checked, see `KeyDistribution::shared`.
+    fn strict(&self) -> bool {
+        !self.distribution.shared()
+    }
+
+    fn contains(&self, index: u64) -> bool {
+        self.erased <= index && index < self.inserted
+    }
+
+    /// Next key index to look up, walking all indices the thread will ever
+    /// insert with a full-period LCG.
+    fn next_find(&mut self) -> u64 {
+        self.find = (self
+            .find
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407))
+            & self.find_mask;
+        self.find
+    }
+
+    /// Maps a rank over the keys of all threads to a `(thread, index)` pair.
+    fn shared(&self, rank: u64) -> (usize, u64) {
+        let threads = self.threads as u64;
+        let thread = (rank % threads) as usize;
+        (thread, scatter(rank / threads, self.find_mask))
+    }
+
+    /// Next key to read, update or upsert according to the distribution.
+    fn next_lookup(&mut self) -> (usize, u64) {
+        let key_space = (self.find_mask + 1) * self.threads as u64;
+
+        match self.distribution {
+            KeyDistribution::Uniform => (self.thread, self.next_find()),
+            KeyDistribution::Zipf(_) => {
+                let rank = self.zipf.as_ref().unwrap().sample(&mut self.rng);
+                self.shared(rank)
+            }
+            KeyDistribution::HotSet { ops, keys } => {
+                let hot = ((key_space as f64 * keys / 100.0) as u64).clamp(1, key_space - 1);
+                let rank = if self.rng.next_f64() * 100.0 < ops {
+                    self.rng.below(hot)
+                } else {
+                    hot + self.rng.below(key_space - hot)
+                };
+                self.shared(rank)
+            }
+            KeyDistribution::Sequential => {
+                if !self.contains(self.find) {
+                    self.find = self.erased;
+                }
+                let index = self.find;
+                self.find += 1;
+                (self.thread, index)
+            }
+            KeyDistribution::Latest(_) => {
+                let rank = self.zipf.as_ref().unwrap().sample(&mut self.rng);
+                (self.thread, self.inserted.saturating_sub(rank + 1))
+            }
+            KeyDistribution::Hot(keys) => {
+                // The first keys of all threads, which prefilling inserts.
+                let rank = self.rng.below(keys);
+                let threads = self.threads as u64;
+                ((rank % threads) as usize, rank / threads)
+            }
+        }
+    }
+}
+
+/// An operation with its key built ahead of time, so that building keys,
+/// which allocates for string keys, stays out of the measurement.
+struct Planned<K> {
+    op: Operation,
+    key: K,
+    index: u64,
+    /// What the map has to report, unless threads share keys.
+    expected: Option<bool>,
+}
+
+/// Picks the key of `op` and what the map has to report for it, updating the
+/// bookkeeping as if the operation had run.
+fn plan<K: From<u64>>(keys: &mut Keys, op: Operation) -> Planned<K> {
+    let (thread, index, expected) = match op {
+        Operation::Read | Operation::Update => {
+            let (thread, index) = keys.next_lookup();
+            (thread, index, keys.contains(index))
+        }
+        Operation::Insert => {
+            let index = keys.inserted;
+            keys.inserted += 1;
+            (keys.thread, index, true)
+        }
+        Operation::Remove => {
+            if keys.erased < keys.inserted {
+                let index = keys.erased;
+                keys.erased += 1;
+                (keys.thread, index, true)
+            } else {
+                (keys.thread, keys.inserted, false)
+            }
+        }
+        Operation::Upsert => {
+            let (thread, mut index) = keys.next_lookup();
+            let fresh = thread == keys.thread && !keys.contains(index);
+            if fresh {
+                index = keys.inserted;
+                keys.inserted += 1;
+            }
+            (thread, index, !fresh)
+        }
+    };
+
+    Planned {
+        op,
+        key: keys.get(thread, index),
+        index,
+        expected: keys.strict().then_some(expected),
+    }
+}
+
+/// Runs a planned operation against the map and returns what the adapter
+/// reported.
+fn execute<H>(handle: &mut H, planned: &Planned<H::Key>) -> bool
+where
+    H: Upsert,
+{
+    let key = &planned.key;
+    let succeeded = match planned.op {
+        Operation::Read => handle.get(key),
+        Operation::Insert => handle.insert(key),
+        Operation::Remove => handle.remove(key),
+        Operation::Update => handle.update(key),
+        Operation::Upsert => handle.upsert(key),
+    };
+
+    if let Some(expected) = planned.expected {
+        assert_eq!(
+            succeeded,
+            expected,
+            "{} of key {}",
+            planned.op.name(),
+            planned.index
+        );
+    }
+    succeeded
+}
+
+/// The operation mix as a shuffled sequence of 100 operations.
+fn mix_sequence(mix: &Mix, rng: &mut impl Rng) -> Vec<Operation> {
+    let shares = [mix.read, mix.insert, mix.remove, mix.update, mix.upsert];
+    let mut sequence: Vec<_> = Operation::ALL
+        .iter()
+        .zip(shares.iter())
+        .flat_map(|(&op, &share)| std::iter::repeat(op).take(usize::from(share)))
+        .collect();
+
+    assert_eq!(sequence.len(), 100, "mix must add up to 100");
+    sequence.shuffle(rng);
+    sequence
+}
+
+/// Decides which operations get timed. Gaps between timed operations are
+/// drawn uniformly from `1..2n` so they average `n` without aliasing with the
+/// 100-operation mix sequence, which would leave some kinds unsampled.
+struct Sampler {
+    every: u32,
+    until: u32,
+    rng: XorShift,
+}
+
+impl Sampler {
+    fn new(every: u32, seed: u64) -> Self {
+        let mut sampler = Self {
+            every,
+            until: 0,
+            rng: XorShift::new(seed),
+        };
+        sampler.until = sampler.gap();
+        sampler
+    }
+
+    fn gap(&mut self) -> u32 {
+        let range = (u64::from(self.every) * 2).saturating_sub(1).max(1);
+        (1 + self.rng.below(range)) as u32
+    }
+
+    fn sample(&mut self) -> bool {
+        if self.every == 0 {
+            return false;
+        }
+
+        self.until -= 1;
+        if self.until == 0 {
+            self.until = self.gap();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// What a worker executes in one phase.
+struct Step {
+    /// Operations per thread, unless the phase runs for `time`.
+    ops: u64,
+    time: Option<Duration>,
+    sequence: Vec<Operation>,
+}
+
+/// How many operations a thread plans at a time, see `Planned`. Phases that
+/// run for a time look at the clock once per batch.
+const BATCH: u64 = 64;
+
+struct Worker {
+    prefill: u64,
+    latency_sample: u32,
+    steps: Vec<Step>,
+}
+
+/// What a worker did in one phase.
+struct WorkerResult {
+    spent: Duration,
+    ops: u64,
+    latencies: [Histogram; 5],
+    counts: [OperationCount; 5],
+    /// Keys of this thread still in the map.
+    live: u64,
+    allocations: AllocCount,
+}
+
+/// A point the workers line up at. Unlike `std::sync::Barrier`, the
+/// measuring thread can give up waiting for them.
+#[derive(Default)]
+struct Gate {
+    /// Arrived workers and whether the gate is open.
+    state: Mutex<(usize, bool)>,
+    changed: Condvar,
+}
+
+impl Gate {
+    fn arrive(&self) {
+        self.state.lock().unwrap().0 += 1;
+        self.changed.notify_all();
+    }
+
+    /// Arrives and blocks until the gate is opened.
+    fn pass(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.0 += 1;
+        self.changed.notify_all();
+
+        while !state.1 {
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    /// Waits for `n` workers to arrive, returns false if `deadline` passed
+    /// first.
+    fn wait_for(&self, n: usize, deadline: Option<Instant>) -> bool {
+        let mut state = self.state.lock().unwrap();
+
+        while state.0 < n {
+            state = match deadline {
+                None => self.changed.wait(state).unwrap(),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return false;
+                    }
+                    self.changed.wait_timeout(state, deadline - now).unwrap().0
+                }
+            };
+        }
+
+        true
+    }
+
+    fn open(&self) {
+        self.state.lock().unwrap().1 = true;
+        self.changed.notify_all();
+    }
+}
+
+/// Operations a worker executed so far and whether it is done, on a cache
+/// line of its own.
+#[derive(Default)]
+#[repr(align(128))]
+struct Progress {
+    ops: AtomicU64,
+    done: AtomicBool,
+}
+
+/// State the workers share with the measuring thread.
+struct Shared {
+    /// Per phase, workers arrive when they are ready for it, after prefilling
+    /// or the previous phase, and start once it opens.
+    started: Vec<Gate>,
+    finished: Vec<Gate>,
+    progress: Vec<Progress>,
+}
+
+impl Worker {
+    fn run<C>(&self, table: &C, shared: &Shared, mut keys: Keys) -> Vec<WorkerResult>
+    where
+        C: Collection,
+        C::Handle: Upsert,
+    {
+        let mut handle = table.pin();
+        let progress = &shared.progress[keys.thread];
+        let mut executed = 0;
+
+        for _ in 0..self.prefill {
+            execute(&mut handle, &plan(&mut keys, Operation::Insert));
+            executed += 1;
+            progress.ops.store(executed, Ordering::Relaxed);
+        }
+
+        let mut sampler = Sampler::new(self.latency_sample, mix64(!keys.seed ^ keys.thread as u64));
+        let mut results = Vec::with_capacity(self.steps.len());
+
+        for (step, (started, finished)) in self
+            .steps
+            .iter()
+            .zip(shared.started.iter().zip(&shared.finished))
+        {
+            let mut latencies: [Histogram; 5] = Default::default();
+            let mut counts = [OperationCount::default(); 5];
+            let mut ops = 0;
+            let mut sequence = step.sequence.iter().cycle();
+            let mut batch = Vec::with_capacity(BATCH as usize);
+            // Time and allocator activity of planning, taken out again below.
+            let mut planning = Duration::from_secs(0);
+            let mut planning_allocations = AllocCount::default();
+
+            started.pass();
+            let allocated = allocator::thread_count();
+            let start = Instant::now();
+            let deadline = step.time.map(|time| start + time);
+
+            loop {
+                let planned = Instant::now();
+                let allocated = allocator::thread_count();
+
+                // Dropping the keys of the last batch is part of planning too.
+                batch.clear();
+                let size = match deadline {
+                    Some(deadline) if planned >= deadline => 0,
+                    Some(_) => BATCH,
+                    None => BATCH.min(step.ops - ops),
+                };
+                batch.extend(
+                    sequence
+                        .by_ref()
+                        .take(size as usize)
+                        .map(|&op| plan(&mut keys, op)),
+                );
+
+                planning_allocations.add(&allocator::thread_count().since(&allocated));
+                planning += planned.elapsed();
+                if batch.is_empty() {
+                    break;
+                }
+
+                for planned in &batch {
+                    let op = planned.op;
+                    let succeeded = if sampler.sample() {
+                        let op_start = Instant::now();
+                        let succeeded = execute(&mut handle, planned);
+                        latencies[op as usize].record(op_start.elapsed().as_nanos() as u64);
+                        succeeded
+                    } else {
+                        execute(&mut handle, planned)
+                    };
+
+                    let count = &mut counts[op as usize];
+                    count.total += 1;
+                    count.succeeded += u64::from(succeeded);
+                    ops += 1;
+                    progress.ops.store(executed + ops, Ordering::Relaxed);
+                }
+            }
+
+            let spent = start.elapsed() - planning;
+            let allocations = allocator::thread_count()
+                .since(&allocated)
+                .since(&planning_allocations);
+            executed += ops;
+            finished.arrive();
+
+            results.push(WorkerResult {
+                spent,
+                ops,
+                latencies,
+                counts,
+                live: keys.inserted.saturating_sub(keys.erased),
+                allocations,
+            });
+        }
+
+        progress.done.store(true, Ordering::Relaxed);
+        results
+    }
+}
+
+/// Watches the threads of a run that missed its deadline for `STALL_WINDOW`.
+fn watch(shared: &Shared, after: Duration, expected: Option<u64>) -> Timeout {
+    let snapshot = || -> Vec<u64> {
+        shared
+            .progress
+            .iter()
+            .map(|progress| progress.ops.load(Ordering::Relaxed))
+            .collect()
+    };
+
+    let before = snapshot();
+    thread::sleep(STALL_WINDOW);
+    let progress = snapshot();
+
+    let finished: Vec<_> = (0..progress.len())
+        .filter(|&thread| shared.progress[thread].done.load(Ordering::Relaxed))
+        .collect();
+    let stalled = before
+        .iter()
+        .zip(&progress)
+        .enumerate()
+        .filter(|(thread, (before, now))| before == now && !finished.contains(thread))
+        .map(|(thread, _)| thread)
+        .collect();
+
+    Timeout {
+        after,
+        expected,
+        progress,
+        finished,
+        stalled,
+    }
+}
+
+/// Runs `workload` against a fresh `C`, replacing `bustle::Workload::run_silently`
+/// with a version that also records per-operation latencies. Returns one
+/// measurement per phase, the warm-up first.
+pub fn run<C>(workload: &Workload) -> Result<Vec<Measurement>, Timeout>
+where
+    C: Collection,
+    C::Handle: Upsert,
+{
+    let threads = workload.threads.max(1);
+    let capacity = workload.capacity();
+    let phases = workload.steps();
+    let prefill = workload.prefill();
+
+    let mut rng = rand::thread_rng();
+    let seed = rng.gen();
+    let steps: Vec<_> = phases
+        .iter()
+        .map(|phase| Step {
+            ops: workload.ops(phase.length),
+            time: match phase.length {
+                Length::Operations(_) => None,
+                Length::Time(time) => Some(time),
+            },
+            sequence: mix_sequence(&phase.mix, &mut rng),
+        })
+        .collect();
+
+    let expected = match steps.iter().any(|step| step.time.is_some()) {
+        true => None,
+        false => Some(prefill + steps.iter().map(|step| step.ops).sum::<u64>()),
+    };
+
+    let worker = Arc::new(Worker {
+        prefill,
+        latency_sample: workload.latency_sample,
+        steps,
+    });
+
+    let peak_reset = memory::reset_peak();
+    let before = memory::rss().unwrap_or(0);
+
+    let started = Instant::now();
+    let deadline = workload.timeout.map(|timeout| started + timeout);
+
+    let table = Arc::new(C::with_capacity(capacity as usize));
+    let shared = Arc::new(Shared {
+        started: phases.iter().map(|_| Gate::default()).collect(),
+        finished: phases.iter().map(|_| Gate::default()).collect(),
+        progress: (0..threads).map(|_| Progress::default()).collect(),
+    });
+
+    let find_mask = workload.find_mask(&phases);
+    let zipf = match workload.distribution {
+        KeyDistribution::Zipf(theta) => Some(Zipf::new((find_mask + 1) * threads as u64, theta)),
+        KeyDistribution::Latest(theta) => Some(Zipf::new(find_mask + 1, theta)),
+        _ => None,
+    };
+
+    let handles: Vec<_> = (0..threads)
+        .map(|thread| {
+            let keys = Keys {
+                seed,
+                thread,
+                threads,
+                erased: 0,
+                inserted: 0,
+                find: 0,
+                find_mask,
+                distribution: workload.distribution,
+                zipf: zipf.clone(),
+                rng: XorShift::new(mix64(seed.rotate_left(32) ^ thread as u64)),
+            };
+            let (worker, table, shared) = (worker.clone(), table.clone(), shared.clone());
+            let cpu = workload.cpus.as_ref().map(|cpus| cpus[thread]);
+            thread::spawn(move || {
+                if let Some(cpu) = cpu {
+                    affinity::pin_thread(cpu).expect("cannot pin worker thread");
+                }
+                worker.run(&*table, &shared, keys)
+            })
+        })
+        .collect();
+
+    let timeout = || watch(&shared, started.elapsed(), expected);
+
+    // Memory at the start and end of every phase, the first one starting
+    // once all threads prefilled.
+    let mut rss = Vec::with_capacity(phases.len());
+    for (start, finish) in shared.started.iter().zip(&shared.finished) {
+        if !start.wait_for(threads, deadline) {
+            return Err(timeout());
+        }
+        let prefilled = memory::rss().unwrap_or(0);
+        start.open();
+
+        if !finish.wait_for(threads, deadline) {
+            return Err(timeout());
+        }
+        let end = memory::rss().unwrap_or(0);
+        let peak = match memory::peak_rss() {
+            Some(peak) if peak_reset => peak,
+            _ => before.max(prefilled).max(end),
+        };
+        rss.push(Memory {
+            before,
+            prefilled,
+            peak,
+            end,
+            ..Memory::default()
+        });
+    }
+
+    let results: Vec<_> = handles
+        .into_iter()
+        .map(|handle| handle.join().expect("worker thread panicked"))
+        .collect();
+
+    drop(table);
+    let dropped = memory::rss().unwrap_or(0);
+
+    let measurements = phases
+        .into_iter()
+        .zip(rss)
+        .enumerate()
+        .map(|(i, (phase, mut memory))| {
+            let mut spent = Duration::from_secs(0);
+            let mut total_ops = 0;
+            let mut latencies: [Histogram; 5] = Default::default();
+            let mut counts = [OperationCount::default(); 5];
+            let mut allocations = AllocCount::default();
+            let mut thread_throughput = Vec::with_capacity(threads);
+
+            for result in results.iter().map(|phases| &phases[i]) {
+                spent = spent.max(result.spent);
+                total_ops += result.ops;
+                for (total, latency) in latencies.iter_mut().zip(&result.latencies) {
+                    total.merge(latency);
+                }
+                for (total, count) in counts.iter_mut().zip(&result.counts) {
+                    total.add(count);
+                }
+                memory.live_entries += result.live;
+                allocations.add(&result.allocations);
+                thread_throughput.push(per_second(result.ops, result.spent));
+            }
+            memory.dropped = dropped;
+
+            Measurement {
+                phase: phase.name,
+                total_ops,
+                spent,
+                throughput: per_second(total_ops, spent),
+                latency: Duration::from_nanos(
+                    (spent.as_nanos() * threads as u128 / u128::from(total_ops.max(1))) as u64,
+                ),
+                latencies,
+                counts,
+                memory,
+                allocations,
+                thread_throughput,
+            }
+        })
+        .collect();
+
+    Ok(measurements)
+}
+
+/// Throughput of `ops` operations in `spent`, 0 if no time was measured, as a
+/// thread that finishes its share within the clock resolution would otherwise
+/// turn the statistics into infinities and NaNs.
+fn per_second(ops: u64, spent: Duration) -> f64 {
+    if spent.is_zero() {
+        return 0.0;
+    }
+    ops as f64 / spent.as_secs_f64()
+}
@@ -0,0 +1,128 @@
+use json::JsonValue;
+
+/// Precision of the histogram: every power of two is split into
+/// `2^SUB_BUCKET_BITS` linear buckets, i.e. values are kept to within ~3%.
+const SUB_BUCKET_BITS: u32 = 5;
+const SUB_BUCKETS: u64 = 1 << SUB_BUCKET_BITS;
+const BUCKETS: usize = (2 * SUB_BUCKETS + (64 - SUB_BUCKET_BITS - 1) as u64 * SUB_BUCKETS) as usize;
+
+/// Log-linear histogram of `u64` values in the spirit of HdrHistogram.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    counts: Vec<u64>,
+    count: u64,
+    sum: u64,
+    max: u64,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: vec![0; BUCKETS],
+            count: 0,
+            sum: 0,
+            max: 0,
+        }
+    }
+}
+
+fn index(value: u64) -> usize {
+    if value < 2 * SUB_BUCKETS {
+        return value as usize;
+    }
+
+    let exp = 63 - value.leading_zeros();
+    let mantissa = value >> (exp - SUB_BUCKET_BITS);
+    (2 * SUB_BUCKETS + u64::from(exp - SUB_BUCKET_BITS - 1) * SUB_BUCKETS + mantissa - SUB_BUCKETS)
+        as usize
+}
+
+/// Largest value that falls into the bucket at `index`.
+fn highest_equivalent(index: usize) -> u64 {
+    let index = index as u64;
+
+    if index < 2 * SUB_BUCKETS {
+        return index;
+    }
+
+    let rel = index - 2 * SUB_BUCKETS;
+    let shift = rel / SUB_BUCKETS + 1;
+    let lowest = (rel % SUB_BUCKETS + SUB_BUCKETS) << shift;
+    lowest.saturating_add((1 << shift) - 1)
+}
+
+impl Histogram {
+    pub fn record(&mut self, value: u64) {
+        self.counts[index(value)] += 1;
+        self.count += 1;
+        self.sum = self.sum.saturating_add(value);
+        self.max = self.max.max(value);
+    }
+
+    pub fn merge(&mut self, other: &Histogram) {
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+        self.count += other.count;
+        self.sum = self.sum.saturating_add(other.sum);
+        self.max = self.max.max(other.max);
+    }
+
+    pub fn mean(&self) -> u64 {
+        self.sum.checked_div(self.count).unwrap_or(0)
+    }
+
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    /// The non-empty buckets, sum and maximum as JSON.
+    pub fn to_json(&self) -> JsonValue {
+        let buckets: Vec<_> = self
+            .counts
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(|(index, &count)| vec![index as u64, count])
+            .collect();
+
+        let mut value = JsonValue::new_object();
+        value["buckets"] = buckets.into();
+        value["sum"] = self.sum.into();
+        value["max"] = self.max.into();
+        value
+    }
+
+    /// Reads back a histogram written by `to_json`.
+    pub fn from_json(value: &JsonValue) -> Option<Histogram> {
+        let mut histogram = Histogram::default();
+        for bucket in value["buckets"].members() {
+            let index = bucket[0].as_usize().filter(|&index| index < BUCKETS)?;
+            let count = bucket[1].as_u64()?;
+            histogram.counts[index] += count;
+            histogram.count += count;
+        }
+        histogram.sum = value["sum"].as_u64()?;
+        histogram.max = value["max"].as_u64()?;
+        Some(histogram)
+    }
+
+    /// Value below which a `quantile` (0..=1) of all recorded values lie.
+    pub fn quantile(&self, quantile: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+
+        let rank = ((quantile * self.count as f64).ceil() as u64).clamp(1, self.count);
+        let mut seen = 0;
+
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return highest_equivalent(index).min(self.max);
+            }
+        }
+
+        self.max
+    }
+}
@@ -0,0 +1,165 @@
+use std::{
+    fmt,
+    str::FromStr,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// Key type the maps are instantiated with.
+///
+/// The harness hands every key a distinct `u64` id. Each key type scrambles
+/// the id with a bijection of its own width, so distinct ids always make
+/// distinct keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum KeyType {
+    #[default]
+    U64,
+    U32,
+    /// ASCII string of the given length.
+    String(usize),
+    Bytes16,
+    Bytes32,
+}
+
+const DEFAULT_STRING_LEN: usize = 16;
+const MIN_STRING_LEN: usize = 8;
+const MAX_STRING_LEN: usize = 4096;
+
+impl fmt::Display for KeyType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyType::U64 => write!(f, "u64"),
+            KeyType::U32 => write!(f, "u32"),
+            KeyType::String(len) => write!(f, "string:{}", len),
+            KeyType::Bytes16 => write!(f, "bytes16"),
+            KeyType::Bytes32 => write!(f, "bytes32"),
+        }
+    }
+}
+
+impl FromStr for KeyType {
+    type Err = String;
+
+    /// Parses `u64`, `u32`, `string[:len]`, `bytes16` and `bytes32`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            None if s == "u64" => Ok(KeyType::U64),
+            None if s == "u32" => Ok(KeyType::U32),
+            None if s == "string" => Ok(KeyType::String(DEFAULT_STRING_LEN)),
+            None if s == "bytes16" => Ok(KeyType::Bytes16),
+            None if s == "bytes32" => Ok(KeyType::Bytes32),
+            Some(("string", len)) => match len.parse() {
+                Ok(len) if (MIN_STRING_LEN..=MAX_STRING_LEN).contains(&len) => {
+                    Ok(KeyType::String(len))
+                }
+                _ => Err(format!(
+                    "string key length must be between {} and {}",
+                    MIN_STRING_LEN, MAX_STRING_LEN
+                )),
+            },
+            _ => Err(format!(
+                "unknown key type '{}', must be one of u64, u32, string[:len], bytes16 or bytes32",
+                s
+            )),
+        }
+    }
+}
+
+/// Length of `StringKey`s. `From<u64>` has no room for parameters, so it is
+/// set once per run by `set_string_len`.
+static STRING_LEN: AtomicUsize = AtomicUsize::new(DEFAULT_STRING_LEN);
+
+pub fn set_string_len(len: usize) {
+    STRING_LEN.store(len, Ordering::Relaxed);
+}
+
+/// splitmix64 finalizer, a bijection on `u64`.
+pub fn mix64(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// murmur3 finalizer, a bijection on `u32`.
+fn mix32(mut h: u32) -> u32 {
+    h ^= h >> 16;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xc2b2_ae35);
+    h ^ (h >> 16)
+}
+
+const MASK_48: u64 = (1 << 48) - 1;
+
+/// A bijection on the low 48 bits: xorshifts and odd multipliers modulo 2^48.
+fn mix48(mut z: u64) -> u64 {
+    z &= MASK_48;
+    z ^= z >> 24;
+    z = z.wrapping_mul(0xd6e8_feb8_6659) & MASK_48;
+    z ^= z >> 23;
+    z = z.wrapping_mul(0x9fb2_1c65_1e99) & MASK_48;
+    z ^ (z >> 24)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct U64Key(u64);
+
+impl From<u64> for U64Key {
+    fn from(id: u64) -> Self {
+        Self(mix64(id))
+    }
+}
+
+/// Only ids that differ in their low 32 bits make distinct keys, so runs with
+/// more than 2^32 key ids are rejected up front.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct U32Key(u32);
+
+impl From<u64> for U32Key {
+    fn from(id: u64) -> Self {
+        Self(mix32(id as u32))
+    }
+}
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// The low 48 bits of the id, scrambled and spelled out in 8 base64 digits
+/// that are repeated up to the configured length.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StringKey(String);
+
+impl From<u64> for StringKey {
+    fn from(id: u64) -> Self {
+        let z = mix48(id);
+        let digits: Vec<u8> = (0..8)
+            .map(|i| ALPHABET[(z >> (6 * i)) as usize & 63])
+            .collect();
+
+        let len = STRING_LEN.load(Ordering::Relaxed);
+        Self(
+            digits
+                .iter()
+                .cycle()
+                .take(len)
+                .map(|&b| char::from(b))
+                .collect(),
+        )
+    }
+}
+
+/// The scrambled id followed by further rounds of scrambling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BytesKey<const N: usize>([u8; N]);
+
+impl<const N: usize> From<u64> for BytesKey<N> {
+    fn from(id: u64) -> Self {
+        let mut bytes = [0; N];
+        let mut z = id;
+
+        for chunk in bytes.chunks_mut(8) {
+            z = mix64(z);
+            chunk.copy_from_slice(&z.to_le_bytes()[..chunk.len()]);
+        }
+
+        Self(bytes)
+    }
+}
@@ -0,0 +1,146 @@
+use json::JsonValue;
+use structopt::StructOpt;
+
+use crate::{bench::HasherKind, registry, sweep, workloads::WorkloadKind};
+
+#[derive(Debug, StructOpt)]
+pub struct Options {
+    /// Print a JSON document instead of plain text.
+    #[structopt(long)]
+    json: bool,
+}
+
+pub fn list(options: &Options) {
+    if options.json {
+        println!("{}", to_json().pretty(2));
+        return;
+    }
+
+    println!("maps:");
+    for adapter in registry::ADAPTERS {
+        let hashers = if adapter.hashers.is_empty() {
+            "-".to_string()
+        } else {
+            hasher_names(adapter.hashers).join(",")
+        };
+
+        let line = format!(
+            "  {:<32} {:<8} hashers={:<10} {}",
+            adapter.name,
+            adapter.kind.name(),
+            hashers,
+            if adapter.default { "" } else { "[opt-in]" },
+        );
+        println!("{}", line.trim_end());
+    }
+
+    println!("workloads:");
+    for workload in WorkloadKind::ALL {
+        println!("  {}", workload.name());
+    }
+
+    println!("hashers:");
+    for hasher in HasherKind::ALL {
+        println!("  {}", hasher.name());
+    }
+
+    println!("distributions:");
+    for distribution in DISTRIBUTIONS {
+        println!("  {}", distribution);
+    }
+
+    println!("key types:");
+    for key_type in KEY_TYPES {
+        println!("  {}", key_type);
+    }
+
+    println!("value types:");
+    for value_type in VALUE_TYPES {
+        println!("  {}", value_type);
+    }
+
+    println!("pin policies:");
+    for pin in PIN_POLICIES {
+        println!("  {}", pin);
+    }
+
+    println!("sweeps:");
+    for sweep in SWEEPS {
+        println!("  {}", sweep);
+    }
+
+    let budget = sweep::budget();
+    let quota = match budget.quota {
+        Some(quota) => format!("{}", quota),
+        None => "none".into(),
+    };
+    println!(
+        "cpus: logical={} physical={} quota={}",
+        budget.logical, budget.physical, quota
+    );
+}
+
+/// Accepted forms of `--distribution`.
+const DISTRIBUTIONS: &[&str] = &[
+    "uniform",
+    "zipf[:theta]",
+    "hotset[:ops/keys]",
+    "sequential",
+    "latest[:theta]",
+    "hot[:keys]",
+];
+
+/// Accepted forms of `--key-type`.
+const KEY_TYPES: &[&str] = &["u64", "u32", "string[:len]", "bytes16", "bytes32"];
+
+/// Accepted forms of `--pin`.
+const PIN_POLICIES: &[&str] = &["none", "compact", "scatter", "list:<cpus>"];
+
+/// Accepted forms of `--sweep`.
+const SWEEPS: &[&str] = &["linear[:step]", "geometric"];
+
+/// Accepted forms of `--value-type`.
+const VALUE_TYPES: &[&str] = &[
+    "u32",
+    "inline:<16|64|256>",
+    "box[:len]",
+    "arc[:len]",
+    "string[:len]",
+];
+
+fn hasher_names(hashers: &[HasherKind]) -> Vec<&'static str> {
+    hashers.iter().map(|hasher| hasher.name()).collect()
+}
+
+fn to_json() -> JsonValue {
+    let mut maps = JsonValue::new_array();
+
+    for adapter in registry::ADAPTERS {
+        let mut map = JsonValue::new_object();
+        map["name"] = adapter.name.into();
+        map["kind"] = adapter.kind.name().into();
+        map["hashers"] = hasher_names(adapter.hashers).into();
+        map["default"] = adapter.default.into();
+        maps.push(map).expect("array push");
+    }
+
+    let workloads: Vec<_> = WorkloadKind::ALL.iter().map(|w| w.name()).collect();
+
+    let mut root = JsonValue::new_object();
+    root["maps"] = maps;
+    root["workloads"] = workloads.into();
+    root["hashers"] = hasher_names(HasherKind::ALL).into();
+    root["distributions"] = DISTRIBUTIONS.into();
+    root["key_types"] = KEY_TYPES.into();
+    root["value_types"] = VALUE_TYPES.into();
+    root["pin_policies"] = PIN_POLICIES.into();
+    root["sweeps"] = SWEEPS.into();
+
+    let budget = sweep::budget();
+    let mut cpus = JsonValue::new_object();
+    cpus["logical"] = budget.logical.into();
+    cpus["physical"] = budget.physical.into();
+    cpus["quota"] = budget.quota.into();
+    root["cpus"] = cpus;
+    root
+}
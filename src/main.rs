@@ -3,15 +3,37 @@ use std::fmt::Debug;
 use structopt::StructOpt;
 
 mod adapters;
+mod affinity;
+mod allocator;
 mod bench;
+mod distribution;
+mod format;
+mod harness;
+mod histogram;
+mod keys;
+mod list;
+mod memory;
+mod metadata;
+mod phases;
 mod plot;
 mod record;
+mod registry;
+mod state;
+mod stats;
+mod suite;
+mod supervisor;
+mod sweep;
+mod values;
 mod workloads;
 
+// Parsed once at startup, the size of `bench::Options` doesn't matter.
+#[allow(clippy::large_enum_variant)]
 #[derive(Debug, StructOpt)]
 enum Options {
     Bench(bench::Options),
     Plot(plot::Options),
+    List(list::Options),
+    Suite(suite::Options),
 }
 
 fn main() {
@@ -20,5 +42,7 @@ fn main() {
     match Options::from_args() {
         Options::Bench(options) => bench::bench(&options),
         Options::Plot(options) => plot::plot(&options),
+        Options::List(options) => list::list(&options),
+        Options::Suite(options) => suite::suite(&options),
     }
 }
@@ -0,0 +1,34 @@
+use std::fs;
+
+/// Value of a `kB` field of `/proc/self/status` in bytes, `None` where procfs
+/// is not available.
+fn status_field(name: &str) -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+
+    status.lines().find_map(|line| {
+        let value = line.strip_prefix(name)?.strip_prefix(':')?;
+        let kb = value
+            .trim()
+            .strip_suffix("kB")?
+            .trim()
+            .parse::<u64>()
+            .ok()?;
+        Some(kb * 1024)
+    })
+}
+
+/// Current resident set size.
+pub fn rss() -> Option<u64> {
+    status_field("VmRSS")
+}
+
+/// Highest resident set size since the last `reset_peak`.
+pub fn peak_rss() -> Option<u64> {
+    status_field("VmHWM")
+}
+
+/// Resets the high water mark reported by `peak_rss` to the current RSS,
+/// returns whether the kernel allowed it.
+pub fn reset_peak() -> bool {
+    fs::write("/proc/self/clear_refs", "5").is_ok()
+}
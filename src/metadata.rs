@@ -0,0 +1,177 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use json::JsonValue;
+
+use crate::{allocator, bench::Options, sweep};
+
+const MANIFEST: &str = include_str!("../Cargo.toml");
+
+/// `<name>.meta.json` next to the result file `<name>.csv`.
+pub fn sidecar(results: &Path) -> PathBuf {
+    results.with_extension("meta.json")
+}
+
+fn read_trimmed(path: &str) -> Option<String> {
+    Some(fs::read_to_string(path).ok()?.trim().to_string())
+}
+
+/// Value of the first `key: value` line of `/proc/cpuinfo` or
+/// `/proc/meminfo` style text.
+fn field<'a>(text: &'a str, key: &str) -> Option<&'a str> {
+    text.lines().find_map(|line| {
+        let (name, value) = line.split_once(':')?;
+        (name.trim() == key).then(|| value.trim())
+    })
+}
+
+fn machine() -> JsonValue {
+    let cpuinfo = fs::read_to_string("/proc/cpuinfo").unwrap_or_default();
+    let meminfo = fs::read_to_string("/proc/meminfo").unwrap_or_default();
+    let budget = sweep::budget();
+
+    let memory = field(&meminfo, "MemTotal")
+        .and_then(|total| total.strip_suffix("kB"))
+        .and_then(|kb| kb.trim().parse::<u64>().ok())
+        .map(|kb| kb * 1024);
+
+    let mut machine = JsonValue::new_object();
+    machine["cpu_model"] = field(&cpuinfo, "model name").into();
+    machine["logical_cpus"] = num_cpus::get().into();
+    machine["physical_cores"] = num_cpus::get_physical().into();
+    machine["available_cpus"] = budget.logical.into();
+    machine["cpu_quota"] = budget.quota.into();
+    machine["memory_bytes"] = memory.into();
+    machine["os"] = env::consts::OS.into();
+    machine["arch"] = env::consts::ARCH.into();
+    machine["kernel"] = read_trimmed("/proc/sys/kernel/osrelease").into();
+    machine["cpu_governor"] =
+        read_trimmed("/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor").into();
+    machine
+}
+
+/// Settings of the `[profile.<name>]` section of the manifest.
+fn profile_settings(profile: &str) -> Vec<&'static str> {
+    let header = format!("[profile.{}]", profile);
+
+    MANIFEST
+        .lines()
+        .skip_while(|line| line.trim() != header)
+        .skip(1)
+        .take_while(|line| !line.trim_start().starts_with('['))
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+fn build() -> JsonValue {
+    let profile = match env!("BUILD_PROFILE") {
+        "debug" => "dev",
+        profile => profile,
+    };
+
+    let mut build = JsonValue::new_object();
+    build["rustc"] = env!("BUILD_RUSTC_VERSION").into();
+    build["profile"] = profile.into();
+    build["opt_level"] = env!("BUILD_OPT_LEVEL").into();
+    build["debug_assertions"] = cfg!(debug_assertions).into();
+    build["profile_settings"] = profile_settings(profile).into();
+    build["count_allocations"] = allocator::ENABLED.into();
+    build["git_commit"] = env!("BUILD_GIT_COMMIT").into();
+    build["git_dirty"] = (env!("BUILD_GIT_DIRTY") == "true").into();
+    build
+}
+
+/// Locked versions of the direct dependencies, which include every map, as
+/// read from `Cargo.lock` by the build script. Empty if there was none.
+fn crates() -> JsonValue {
+    let mut crates = JsonValue::new_object();
+    for dependency in env!("BUILD_CRATES").split(',').filter(|d| !d.is_empty()) {
+        let mut parts = dependency.split(' ');
+        let name = parts.next().unwrap_or_default();
+        crates[name] = parts.next().into();
+    }
+    crates
+}
+
+/// Everything needed to tell where and how a result set was produced.
+pub fn collect(options: &Options) -> JsonValue {
+    let created = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs());
+
+    let mut meta = JsonValue::new_object();
+    meta["created"] = created.into();
+    meta["machine"] = machine();
+    meta["build"] = build();
+    meta["crates"] = crates();
+    meta["command"] = env::args().collect::<Vec<_>>().into();
+    meta["options"] = options.to_args().into();
+    meta
+}
+
+/// Metadata of every run that wrote to a result file, from its sidecar. A
+/// sidecar holding a single object is one run.
+pub fn runs(sidecar: &JsonValue) -> Vec<&JsonValue> {
+    if sidecar.is_array() {
+        sidecar.members().collect()
+    } else {
+        vec![sidecar]
+    }
+}
+
+/// Writes the metadata of a run with `options` next to its result file,
+/// after that of the earlier runs if the run `appends` to the file.
+pub fn write(results: &Path, options: &Options, appends: bool) {
+    let path = sidecar(results);
+    let mut sidecar = JsonValue::new_array();
+
+    if appends {
+        let earlier = fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| json::parse(&text).ok());
+        for run in earlier.iter().flat_map(runs) {
+            sidecar.push(run.clone()).expect("not an array");
+        }
+    }
+
+    sidecar.push(collect(options)).expect("not an array");
+    fs::write(&path, sidecar.pretty(2))
+        .unwrap_or_else(|e| panic!("cannot write {}: {}", path.display(), e));
+}
+
+/// One line naming the machine and build of `meta`, for plots.
+pub fn summary(meta: &JsonValue) -> String {
+    let machine = &meta["machine"];
+    let build = &meta["build"];
+    let mut parts = Vec::new();
+
+    if let Some(cpu) = machine["cpu_model"].as_str() {
+        parts.push(cpu.to_string());
+    }
+    if let (Some(cores), Some(cpus)) = (
+        machine["physical_cores"].as_u32(),
+        machine["logical_cpus"].as_u32(),
+    ) {
+        parts.push(format!("{}C/{}T", cores, cpus));
+    }
+    if let (Some(os), Some(kernel)) = (machine["os"].as_str(), machine["kernel"].as_str()) {
+        parts.push(format!("{} {}", os, kernel));
+    }
+    if let Some(rustc) = build["rustc"].as_str() {
+        parts.push(rustc.to_string());
+    }
+    if let Some(commit) = build["git_commit"].as_str().filter(|c| !c.is_empty()) {
+        let dirty = if build["git_dirty"].as_bool() == Some(true) {
+            "+dirty"
+        } else {
+            ""
+        };
+        parts.push(format!("{}{}", &commit[..commit.len().min(10)], dirty));
+    }
+
+    parts.join(" | ")
+}
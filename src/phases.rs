@@ -0,0 +1,174 @@
+use std::{fmt, str::FromStr, time::Duration};
+
+use bustle::Mix;
+
+/// How long a phase runs.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    /// Operations as a multiple of the initial capacity, split over the
+    /// threads.
+    Operations(f64),
+    /// Every thread runs until this much time has passed.
+    Time(Duration),
+}
+
+/// Units of a time with their size in nanoseconds, largest first.
+const UNITS: [(&str, u64); 4] = [
+    ("s", 1_000_000_000),
+    ("ms", 1_000_000),
+    ("us", 1_000),
+    ("ns", 1),
+];
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Length::Operations(multiplier) => write!(f, "{}", multiplier),
+            Length::Time(time) => {
+                // The largest unit that holds the time exactly, so that it
+                // reads back the same.
+                let nanos = time.as_nanos();
+                let (unit, size) = UNITS
+                    .iter()
+                    .find(|(_, size)| nanos % u128::from(*size) == 0)
+                    .expect("nanoseconds divide every time");
+                write!(f, "{}{}", nanos / u128::from(*size), unit)
+            }
+        }
+    }
+}
+
+impl FromStr for Length {
+    type Err = String;
+
+    /// Parses an operation multiplier like `0.5` or a duration like `30s`,
+    /// `2.5s`, `500ms`, `250us` or `800ns`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid length '{}'", s);
+
+        // Smallest unit first, as every other unit also ends in `s`.
+        let time = UNITS
+            .iter()
+            .rev()
+            .find_map(|&(unit, size)| Some((s.strip_suffix(unit)?, size)));
+        let length = match time {
+            Some((secs, 1_000_000_000)) => {
+                let secs: f64 = secs.parse().map_err(|_| invalid())?;
+                if !secs.is_finite() || secs < 0.0 {
+                    return Err(invalid());
+                }
+                Length::Time(Duration::from_secs_f64(secs))
+            }
+            Some((count, size)) => {
+                let count: u64 = count.parse().map_err(|_| invalid())?;
+                let nanos = count.checked_mul(size).ok_or_else(invalid)?;
+                Length::Time(Duration::from_nanos(nanos))
+            }
+            None => Length::Operations(s.parse().map_err(|_| invalid())?),
+        };
+
+        match length {
+            Length::Operations(multiplier) if multiplier > 0.0 && multiplier.is_finite() => {
+                Ok(length)
+            }
+            Length::Time(time) if !time.is_zero() => Ok(length),
+            _ => Err(format!("length '{}' must be positive", s)),
+        }
+    }
+}
+
+/// Parses a time like `30s`, `2.5s`, `500ms` or `250us`.
+pub fn parse_time(s: &str) -> Result<Duration, String> {
+    match s.parse()? {
+        Length::Time(time) => Ok(time),
+        Length::Operations(_) => Err(format!("'{}' is not a time like 30s or 500ms", s)),
+    }
+}
+
+/// Formats a time the way `parse_time` reads it.
+pub fn format_time(time: Duration) -> String {
+    Length::Time(time).to_string()
+}
+
+/// A step of a phased run: `mix` runs for `length` against the map the
+/// previous phases left behind.
+#[derive(Debug, Clone)]
+pub struct Phase {
+    pub name: String,
+    pub mix: Mix,
+    pub length: Length,
+}
+
+/// Operation kinds in the order of `shares`.
+const KINDS: [&str; 5] = ["read", "insert", "remove", "update", "upsert"];
+
+fn shares(mix: &Mix) -> [u8; 5] {
+    [mix.read, mix.insert, mix.remove, mix.update, mix.upsert]
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mix: Vec<_> = KINDS
+            .iter()
+            .zip(shares(&self.mix))
+            .filter(|(_, share)| *share > 0)
+            .map(|(kind, share)| format!("{}={}", kind, share))
+            .collect();
+
+        write!(f, "{}:{}:{}", self.name, mix.join(","), self.length)
+    }
+}
+
+impl FromStr for Phase {
+    type Err = String;
+
+    /// Parses `<name>:<op>=<percent>[,<op>=<percent>...]:<length>`, e.g.
+    /// `churn:read=20,insert=40,remove=40:30s`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, ':');
+        let (name, mix, length) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(name), Some(mix), Some(length)) if !name.is_empty() => (name, mix, length),
+            _ => {
+                return Err(format!(
+                    "invalid phase '{}', must be '<name>:<op>=<percent>,...:<length>'",
+                    s
+                ))
+            }
+        };
+
+        let mut percents = [0u8; 5];
+        for share in mix.split(',') {
+            let (kind, percent) = share
+                .split_once('=')
+                .ok_or_else(|| format!("invalid share '{}' in phase '{}'", share, name))?;
+            let index = KINDS
+                .iter()
+                .position(|&k| k == kind)
+                .ok_or_else(|| format!("unknown operation '{}' in phase '{}'", kind, name))?;
+            percents[index] = percent
+                .parse()
+                .map_err(|_| format!("invalid percentage '{}' in phase '{}'", percent, name))?;
+        }
+
+        let total: u32 = percents.iter().map(|&p| u32::from(p)).sum();
+        if total != 100 {
+            return Err(format!(
+                "mix of phase '{}' adds up to {}, not 100",
+                name, total
+            ));
+        }
+
+        let [read, insert, remove, update, upsert] = percents;
+        Ok(Phase {
+            name: name.into(),
+            mix: Mix {
+                read,
+                insert,
+                remove,
+                update,
+                upsert,
+            },
+            length: length.parse()?,
+        })
+    }
+}
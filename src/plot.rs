@@ -1,9 +1,21 @@
-use std::{collections::BTreeMap, error::Error, io, path::PathBuf, time::Duration};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    error::Error,
+    fs::{self, File},
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
 
-use plotters::prelude::*;
-use structopt::StructOpt;
+use plotters::{coord::Shift, prelude::*};
+use structopt::{clap, StructOpt};
 
-use crate::record::Record;
+use crate::{
+    format::{self, Format},
+    metadata,
+    record::Record,
+};
 
 const FONT: &str = "Fira Code";
 
@@ -11,6 +23,10 @@ const FONT: &str = "Fira Code";
 pub struct Options {
     // <dir>/<name>.throughput.svg
     // <dir>/<name>.latency.svg
+    // <dir>/<name>.memory.svg (if the records carry memory usage)
+    // <dir>/<name>.fairness.svg (if the records carry thread fairness)
+    // <dir>/<name>.hot-keys.svg (if they carry several hot key counts)
+    // <dir>/<name>.percentiles.svg (with `--percentiles`)
     dir: PathBuf,
     name: String,
     #[structopt(short, long, default_value = "640")]
@@ -19,55 +35,293 @@ pub struct Options {
     height: u32,
     #[structopt(long, default_value = "2000")]
     latency_limit_ns: u64,
+    /// Spread drawn around repeated measurements: ci95, stddev, minmax or none.
+    #[structopt(long, default_value = "ci95")]
+    error_bars: ErrorBars,
+    /// Also plot the latency percentiles of the runs with this many threads.
+    #[structopt(long)]
+    percentiles: Option<u32>,
+    /// Read the records from these files instead of stdin.
+    #[structopt(short, long, parse(from_os_str))]
+    input: Vec<PathBuf>,
+    /// Format of the records: csv, json or jsonl. Taken from the extension of
+    /// every input file when not given, csv for stdin.
+    #[structopt(long)]
+    format: Option<Format>,
+    /// Only plot the records of this phase of a `Phased` run.
+    #[structopt(long)]
+    phase: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ErrorBars {
+    Ci95,
+    Stddev,
+    MinMax,
+    None,
+}
+
+impl FromStr for ErrorBars {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ci95" => Ok(Self::Ci95),
+            "stddev" => Ok(Self::Stddev),
+            "minmax" => Ok(Self::MinMax),
+            "none" => Ok(Self::None),
+            _ => Err("invalid error bars, must be one of 'ci95', 'stddev', 'minmax' or 'none'"),
+        }
+    }
+}
+
+/// Lower and upper end of the throughput error bar.
+fn throughput_range(record: &Record, bars: ErrorBars) -> (f64, f64) {
+    let mean = record.throughput;
+
+    match bars {
+        ErrorBars::Ci95 => (mean - record.throughput_ci95, mean + record.throughput_ci95),
+        ErrorBars::Stddev => (
+            mean - record.throughput_stddev,
+            mean + record.throughput_stddev,
+        ),
+        ErrorBars::MinMax => (record.throughput_min, record.throughput_max),
+        ErrorBars::None => (mean, mean),
+    }
+}
+
+/// Lower and upper end of the latency error bar.
+fn latency_range(record: &Record, bars: ErrorBars) -> (Duration, Duration) {
+    let mean = record.latency;
+
+    match bars {
+        ErrorBars::Ci95 => (
+            mean.saturating_sub(record.latency_ci95),
+            mean + record.latency_ci95,
+        ),
+        ErrorBars::Stddev => (
+            mean.saturating_sub(record.latency_stddev),
+            mean + record.latency_stddev,
+        ),
+        ErrorBars::MinMax => (record.latency_min, record.latency_max),
+        ErrorBars::None => (mean, mean),
+    }
+}
+
+/// Records with a spread worth drawing, i.e. measured more than once.
+fn repeated(records: &[Record], bars: ErrorBars) -> impl Iterator<Item = &Record> {
+    records
+        .iter()
+        .filter(move |record| bars != ErrorBars::None && record.repetitions > 1)
 }
 
 pub fn plot(options: &Options) {
-    let data = read_data();
+    let data = read_data(options);
     let data = group_data(data);
     plot_throughput(options, &data).expect("failed to plot throughput");
     plot_latency(options, &data).expect("failed to plot latency");
+
+    if data
+        .values()
+        .flatten()
+        .any(|record| record.bytes_per_entry > 0.0)
+    {
+        plot_memory(options, &data).expect("failed to plot memory");
+    }
+
+    if data
+        .values()
+        .flatten()
+        .any(|record| record.threads > 1 && record.fairness > 0.0)
+    {
+        plot_fairness(options, &data).expect("failed to plot fairness");
+    }
+
+    if hot_keys(data.values().flatten()).len() > 1 {
+        plot_hot_keys(options, &data).expect("failed to plot hot keys");
+    }
+
+    if let Some(threads) = options.percentiles {
+        plot_percentiles(options, &data, threads).expect("failed to plot percentiles");
+    }
 }
 
-fn read_data() -> Vec<Record> {
-    let rd = io::stdin();
-    let mut rd = csv::Reader::from_reader(rd);
+/// Format of the input file `path`, from its extension.
+fn input_format(path: &Path) -> Format {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("json") => Format::Json,
+        Some("jsonl") => Format::Jsonl,
+        _ => Format::Csv,
+    }
+}
+
+fn read_data(options: &Options) -> Vec<Record> {
+    let fail = |e: String| -> ! {
+        clap::Error::with_description(&e, clap::ErrorKind::InvalidValue).exit()
+    };
 
-    rd.deserialize()
-        .map(|result| result.expect("invalid record"))
+    let mut records = Vec::new();
+    if options.input.is_empty() {
+        let format = options.format.unwrap_or(Format::Csv);
+        records = format::read(format, io::stdin()).unwrap_or_else(|e| fail(e));
+    }
+    for path in &options.input {
+        let format = options.format.unwrap_or_else(|| input_format(path));
+        let file = File::open(path)
+            .unwrap_or_else(|e| fail(format!("cannot open {}: {}", path.display(), e)));
+        let read = format::read(format, file)
+            .unwrap_or_else(|e| fail(format!("{}: {}", path.display(), e)));
+        records.extend(read);
+    }
+
+    records
+        .into_iter()
+        .filter(|record| record.status == "ok")
+        .filter(|record| match &options.phase {
+            Some(phase) => &record.phase == phase,
+            None => true,
+        })
         .collect()
 }
 
+/// Records per line, labelled with the map and, for phased runs, the phase.
 type Groups = BTreeMap<String, Vec<Record>>;
 
+/// Distinct hot key counts of the records of a `hot` distribution.
+fn hot_keys<'a>(records: impl IntoIterator<Item = &'a Record>) -> BTreeSet<u64> {
+    records
+        .into_iter()
+        .map(|record| record.hot_keys)
+        .filter(|&keys| keys > 0)
+        .collect()
+}
+
 fn group_data(records: Vec<Record>) -> Groups {
     let mut groups = Groups::new();
+    let several_hot_keys = hot_keys(&records).len() > 1;
 
     for record in records {
-        let group = groups.entry(record.name.clone()).or_insert_with(Vec::new);
-        group.push(record);
+        let mut label = if record.phase.is_empty() {
+            record.name.clone()
+        } else {
+            format!("{} {}", record.name, record.phase)
+        };
+        if several_hot_keys {
+            label = format!("{} k={}", label, record.hot_keys);
+        }
+        groups.entry(label).or_insert_with(Vec::new).push(record);
     }
 
     groups
 }
 
-static COLORS: &[RGBColor] = &[BLUE, RED, GREEN, MAGENTA, CYAN, BLACK, YELLOW];
+type Field = fn(&Record) -> &String;
 
-fn plot_throughput(options: &Options, groups: &Groups) -> Result<(), Box<dyn Error>> {
-    let path = format!("{}/{}.throughput.svg", options.dir.display(), options.name);
+/// Value of `field` if all records agree on it.
+fn common(groups: &Groups, field: Field) -> Option<&String> {
+    let mut values = groups.values().flatten().map(field);
+    let first = values.next()?;
+    values.all(|other| other == first).then_some(first)
+}
+
+/// Chart caption, naming the workload when the records carry a custom one,
+/// the phase when all records are of one and the key distribution, key and
+/// value type when they are not the defaults.
+fn caption(options: &Options, groups: &Groups, metric: &str) -> String {
+    let mut caption = options.name.clone();
+
+    match common(groups, |record| &record.workload) {
+        Some(workload) if workload.starts_with("Custom") || workload.starts_with("Phased(") => {
+            caption = format!("{} {}", caption, workload);
+        }
+        _ => {}
+    }
+
+    let dimensions: [(&str, &str, Field); 5] = [
+        ("phase", "", |record| &record.phase),
+        ("keys", "uniform", |record| &record.distribution),
+        ("key_type", "u64", |record| &record.key_type),
+        ("value_type", "u32", |record| &record.value_type),
+        ("pin", "none", |record| &record.pin),
+    ];
+
+    for (label, default, field) in dimensions {
+        match common(groups, field) {
+            Some(value) if value != default => {
+                caption = format!("{} {}={}", caption, label, value);
+            }
+            _ => {}
+        }
+    }
+
+    format!("{}: {}", caption, metric)
+}
+
+/// Metadata file of the records: the sidecar of the first `--input` file that
+/// has one, or `<dir>/<name>.meta.json` for records read from stdin.
+fn sidecar(options: &Options) -> Option<PathBuf> {
+    if options.input.is_empty() {
+        return Some(options.dir.join(format!("{}.meta.json", options.name)));
+    }
+
+    options
+        .input
+        .iter()
+        .map(|path| metadata::sidecar(path))
+        .find(|sidecar| sidecar.exists())
+}
+
+/// Drawing area of the plot at `path`, above a footer naming the machines and
+/// builds from the metadata of the records if there is any, see `sidecar`.
+fn canvas<'a>(
+    options: &Options,
+    path: &'a str,
+) -> Result<DrawingArea<SVGBackend<'a>, Shift>, Box<dyn Error>> {
     let resolution = (options.width, options.height);
-    let root = SVGBackend::new(&path, resolution).into_drawing_area();
+    let root = SVGBackend::new(path, resolution).into_drawing_area();
 
     root.fill(&WHITE)?;
 
+    let meta = match sidecar(options).map(fs::read_to_string) {
+        Some(Ok(text)) => json::parse(&text)?,
+        _ => return Ok(root),
+    };
+
+    // Runs appended to the same file on other machines or builds are all
+    // named.
+    let mut summaries: Vec<_> = metadata::runs(&meta)
+        .into_iter()
+        .map(metadata::summary)
+        .collect();
+    summaries.dedup();
+
+    let (chart, footer) = root.split_vertically(options.height.saturating_sub(20));
+    footer.draw(&Text::new(
+        summaries.join("; "),
+        (10, 2),
+        (FONT, 12).into_font().color(&BLACK.mix(0.6)),
+    ))?;
+    Ok(chart)
+}
+
+static COLORS: &[RGBColor] = &[BLUE, RED, GREEN, MAGENTA, CYAN, BLACK, YELLOW];
+
+fn plot_throughput(options: &Options, groups: &Groups) -> Result<(), Box<dyn Error>> {
+    let path = format!("{}/{}.throughput.svg", options.dir.display(), options.name);
+    let root = canvas(options, &path)?;
+
     let (x_max, y_max) = groups
         .values()
         .flatten()
-        .map(|record| (record.threads, record.throughput))
+        .map(|record| {
+            let (_, upper) = throughput_range(record, options.error_bars);
+            (record.threads, upper.max(record.throughput))
+        })
         .fold((0, 0f64), |res, cur| (res.0.max(cur.0), res.1.max(cur.1)));
 
     let mut chart = ChartBuilder::on(&root)
         .margin(10)
-        .caption(&format!("{}: Throughput", options.name), (FONT, 20))
+        .caption(&caption(options, groups, "Throughput"), (FONT, 20))
         .set_label_area_size(LabelAreaPosition::Left, 70)
         .set_label_area_size(LabelAreaPosition::Right, 70)
         .set_label_area_size(LabelAreaPosition::Bottom, 40)
@@ -85,7 +339,7 @@ fn plot_throughput(options: &Options, groups: &Groups) -> Result<(), Box<dyn Err
 
     let colors = COLORS.iter().cycle();
 
-    for (records, color) in groups.values().zip(colors) {
+    for ((label, records), color) in groups.iter().zip(colors) {
         chart
             .draw_series(LineSeries::new(
                 records
@@ -93,8 +347,13 @@ fn plot_throughput(options: &Options, groups: &Groups) -> Result<(), Box<dyn Err
                     .map(|record| (record.threads, record.throughput)),
                 color,
             ))?
-            .label(&records[0].name)
+            .label(label)
             .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+
+        chart.draw_series(repeated(records, options.error_bars).map(|record| {
+            let (lower, upper) = throughput_range(record, options.error_bars);
+            ErrorBar::new_vertical(record.threads, lower, record.throughput, upper, color, 6)
+        }))?;
     }
 
     chart
@@ -110,15 +369,15 @@ fn plot_throughput(options: &Options, groups: &Groups) -> Result<(), Box<dyn Err
 
 fn plot_latency(options: &Options, groups: &Groups) -> Result<(), Box<dyn Error>> {
     let path = format!("{}/{}.latency.svg", options.dir.display(), options.name);
-    let resolution = (options.width, options.height);
-    let root = SVGBackend::new(&path, resolution).into_drawing_area();
-
-    root.fill(&WHITE)?;
+    let root = canvas(options, &path)?;
 
     let (x_max, y_max) = groups
         .values()
         .flatten()
-        .map(|record| (record.threads, record.latency))
+        .map(|record| {
+            let (_, upper) = latency_range(record, options.error_bars);
+            (record.threads, upper.max(record.latency))
+        })
         .fold((0, Duration::from_secs(0)), |res, cur| {
             (res.0.max(cur.0), res.1.max(cur.1))
         });
@@ -127,7 +386,7 @@ fn plot_latency(options: &Options, groups: &Groups) -> Result<(), Box<dyn Error>
 
     let mut chart = ChartBuilder::on(&root)
         .margin(10)
-        .caption(&format!("{}: Latency", options.name), (FONT, 20))
+        .caption(&caption(options, groups, "Latency"), (FONT, 20))
         .set_label_area_size(LabelAreaPosition::Left, 70)
         .set_label_area_size(LabelAreaPosition::Right, 70)
         .set_label_area_size(LabelAreaPosition::Bottom, 40)
@@ -146,7 +405,7 @@ fn plot_latency(options: &Options, groups: &Groups) -> Result<(), Box<dyn Error>
 
     let colors = COLORS.iter().cycle();
 
-    for (records, color) in groups.values().zip(colors) {
+    for ((label, records), color) in groups.iter().zip(colors) {
         chart
             .draw_series(LineSeries::new(
                 records
@@ -154,7 +413,298 @@ fn plot_latency(options: &Options, groups: &Groups) -> Result<(), Box<dyn Error>
                     .map(|record| (record.threads, record.latency.as_nanos() as u64)),
                 color,
             ))?
-            .label(&records[0].name)
+            .label(label)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+
+        chart.draw_series(repeated(records, options.error_bars).map(|record| {
+            let (lower, upper) = latency_range(record, options.error_bars);
+            ErrorBar::new_vertical(
+                record.threads,
+                lower.as_nanos() as u64,
+                record.latency.as_nanos() as u64,
+                upper.as_nanos() as u64,
+                color,
+                6,
+            )
+        }))?;
+    }
+
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperLeft)
+        .label_font((FONT, 13))
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+
+    Ok(())
+}
+
+static PERCENTILES: &[&str] = &["p50", "p90", "p99", "p99.9", "max"];
+
+fn percentiles(record: &Record) -> [u64; 5] {
+    [
+        record.all_p50,
+        record.all_p90,
+        record.all_p99,
+        record.all_p999,
+        record.all_max,
+    ]
+    .map(|latency| latency.as_nanos() as u64)
+}
+
+fn plot_memory(options: &Options, groups: &Groups) -> Result<(), Box<dyn Error>> {
+    let path = format!("{}/{}.memory.svg", options.dir.display(), options.name);
+    let root = canvas(options, &path)?;
+
+    let (x_max, y_max) = groups
+        .values()
+        .flatten()
+        .map(|record| (record.threads, record.bytes_per_entry))
+        .fold((0, 0f64), |res, cur| (res.0.max(cur.0), res.1.max(cur.1)));
+
+    let mut chart = ChartBuilder::on(&root)
+        .margin(10)
+        .caption(&caption(options, groups, "Memory"), (FONT, 20))
+        .set_label_area_size(LabelAreaPosition::Left, 70)
+        .set_label_area_size(LabelAreaPosition::Right, 70)
+        .set_label_area_size(LabelAreaPosition::Bottom, 40)
+        .build_cartesian_2d(1..x_max, 0.0..y_max * 1.1)?;
+
+    chart
+        .configure_mesh()
+        .disable_y_mesh()
+        .x_label_formatter(&|v| format!("{}", v))
+        .y_label_formatter(&|v| format!("{:.0} B", v))
+        .x_labels(20)
+        .y_desc("Bytes per entry")
+        .x_desc("Threads")
+        .draw()?;
+
+    let colors = COLORS.iter().cycle();
+
+    for ((label, records), color) in groups.iter().zip(colors) {
+        chart
+            .draw_series(LineSeries::new(
+                records
+                    .iter()
+                    .map(|record| (record.threads, record.bytes_per_entry)),
+                color,
+            ))?
+            .label(label)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+    }
+
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::UpperLeft)
+        .label_font((FONT, 13))
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+
+    Ok(())
+}
+
+fn plot_fairness(options: &Options, groups: &Groups) -> Result<(), Box<dyn Error>> {
+    let path = format!("{}/{}.fairness.svg", options.dir.display(), options.name);
+    let root = canvas(options, &path)?;
+
+    let x_max = groups
+        .values()
+        .flatten()
+        .map(|record| record.threads)
+        .max()
+        .unwrap_or(1);
+
+    let mut chart = ChartBuilder::on(&root)
+        .margin(10)
+        .caption(&caption(options, groups, "Fairness"), (FONT, 20))
+        .set_label_area_size(LabelAreaPosition::Left, 70)
+        .set_label_area_size(LabelAreaPosition::Right, 70)
+        .set_label_area_size(LabelAreaPosition::Bottom, 40)
+        .build_cartesian_2d(1..x_max, 0.0..1.05)?;
+
+    chart
+        .configure_mesh()
+        .disable_y_mesh()
+        .x_label_formatter(&|v| format!("{}", v))
+        .y_label_formatter(&|v| format!("{:.1}", v))
+        .x_labels(20)
+        .y_desc("Jain's fairness index")
+        .x_desc("Threads")
+        .draw()?;
+
+    let colors = COLORS.iter().cycle();
+
+    for ((label, records), color) in groups.iter().zip(colors) {
+        chart
+            .draw_series(LineSeries::new(
+                records
+                    .iter()
+                    .filter(|record| record.fairness > 0.0)
+                    .map(|record| (record.threads, record.fairness)),
+                color,
+            ))?
+            .label(label)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+    }
+
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::LowerLeft)
+        .label_font((FONT, 13))
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+
+    Ok(())
+}
+
+/// Fairness over the number of hot keys, at the largest thread count.
+fn plot_hot_keys(options: &Options, groups: &Groups) -> Result<(), Box<dyn Error>> {
+    let path = format!("{}/{}.hot-keys.svg", options.dir.display(), options.name);
+    let root = canvas(options, &path)?;
+
+    let threads = groups
+        .values()
+        .flatten()
+        .map(|record| record.threads)
+        .max()
+        .unwrap_or(1);
+
+    // Lines per map rather than per group, which tell the counts apart.
+    let mut lines: BTreeMap<String, Vec<(u64, f64)>> = BTreeMap::new();
+    for record in groups.values().flatten() {
+        if record.threads == threads && record.hot_keys > 0 && record.fairness > 0.0 {
+            let label = if record.phase.is_empty() {
+                record.name.clone()
+            } else {
+                format!("{} {}", record.name, record.phase)
+            };
+            lines
+                .entry(label)
+                .or_default()
+                .push((record.hot_keys, record.fairness));
+        }
+    }
+
+    let x_max = hot_keys(groups.values().flatten())
+        .into_iter()
+        .max()
+        .unwrap_or(1)
+        .max(2);
+
+    let mut chart = ChartBuilder::on(&root)
+        .margin(10)
+        .caption(
+            &caption(
+                options,
+                groups,
+                &format!("Fairness over hot keys ({} threads)", threads),
+            ),
+            (FONT, 20),
+        )
+        .set_label_area_size(LabelAreaPosition::Left, 70)
+        .set_label_area_size(LabelAreaPosition::Right, 70)
+        .set_label_area_size(LabelAreaPosition::Bottom, 40)
+        .build_cartesian_2d((1..x_max).log_scale(), 0.0..1.05)?;
+
+    chart
+        .configure_mesh()
+        .disable_y_mesh()
+        .x_label_formatter(&|v| format!("{}", v))
+        .y_label_formatter(&|v| format!("{:.1}", v))
+        .y_desc("Jain's fairness index")
+        .x_desc("Hot keys")
+        .draw()?;
+
+    let colors = COLORS.iter().cycle();
+
+    for ((label, mut points), color) in lines.into_iter().zip(colors) {
+        points.sort_by_key(|&(keys, _)| keys);
+        chart
+            .draw_series(LineSeries::new(points, color))?
+            .label(label)
+            .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
+    }
+
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::LowerRight)
+        .label_font((FONT, 13))
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+
+    Ok(())
+}
+
+fn plot_percentiles(
+    options: &Options,
+    groups: &Groups,
+    threads: u32,
+) -> Result<(), Box<dyn Error>> {
+    let path = format!("{}/{}.percentiles.svg", options.dir.display(), options.name);
+    let root = canvas(options, &path)?;
+
+    let find = |records: &[Record]| {
+        records
+            .iter()
+            .find(|record| record.threads == threads && !record.all_max.is_zero())
+            .map(percentiles)
+    };
+
+    let y_max = groups
+        .values()
+        .filter_map(|records| find(records))
+        .map(|values| values[values.len() - 1])
+        .max()
+        .unwrap_or(1)
+        .max(2);
+
+    let mut chart = ChartBuilder::on(&root)
+        .margin(10)
+        .caption(
+            &caption(
+                options,
+                groups,
+                &format!("Latency percentiles ({} threads)", threads),
+            ),
+            (FONT, 20),
+        )
+        .set_label_area_size(LabelAreaPosition::Left, 70)
+        .set_label_area_size(LabelAreaPosition::Right, 70)
+        .set_label_area_size(LabelAreaPosition::Bottom, 40)
+        .build_cartesian_2d(0..PERCENTILES.len() - 1, (1..y_max).log_scale())?;
+
+    chart
+        .configure_mesh()
+        .disable_y_mesh()
+        .x_labels(PERCENTILES.len())
+        .x_label_formatter(&|&i| PERCENTILES[i].to_string())
+        .y_label_formatter(&|v| format!("{:.0} ns", v))
+        .y_desc("Latency")
+        .x_desc("Percentile")
+        .draw()?;
+
+    let colors = COLORS.iter().cycle();
+
+    for ((label, records), color) in groups.iter().zip(colors) {
+        let values = match find(records) {
+            Some(values) => values,
+            None => continue,
+        };
+
+        chart
+            .draw_series(LineSeries::new(
+                values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &value)| (i, value.max(1))),
+                color,
+            ))?
+            .label(label)
             .legend(move |(x, y)| PathElement::new(vec![(x, y), (x + 20, y)], color));
     }
 
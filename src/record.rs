@@ -2,7 +2,10 @@ use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize)]
+/// One `(map, threads)` point. With repetitions, `spent`, `throughput` and
+/// `latency` are means over all runs and the remaining columns describe
+/// their spread.
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Record {
     pub name: String,
     pub total_ops: u64,
@@ -12,6 +15,229 @@ pub struct Record {
     pub throughput: f64,
     #[serde(with = "timestamp")]
     pub latency: Duration,
+    #[serde(default)]
+    pub workload: String,
+    /// Phase of a `Phased` run the record measures, empty otherwise.
+    #[serde(default)]
+    pub phase: String,
+    #[serde(default)]
+    pub hasher: String,
+    #[serde(default = "uniform")]
+    pub distribution: String,
+    #[serde(default = "u64")]
+    pub key_type: String,
+    #[serde(default = "u32")]
+    pub value_type: String,
+    /// Thread placement policy and the CPU of every thread, in thread order.
+    #[serde(default = "none")]
+    pub pin: String,
+    #[serde(default)]
+    pub cpus: String,
+    /// `ok`, or why the point has no measurement, e.g. `crashed: ...`.
+    #[serde(default = "ok")]
+    pub status: String,
+    #[serde(default = "one")]
+    pub repetitions: u32,
+    #[serde(default)]
+    pub throughput_median: f64,
+    #[serde(default)]
+    pub throughput_stddev: f64,
+    #[serde(default)]
+    pub throughput_min: f64,
+    #[serde(default)]
+    pub throughput_max: f64,
+    #[serde(default)]
+    pub throughput_ci95: f64,
+    #[serde(default, with = "timestamp")]
+    pub latency_median: Duration,
+    #[serde(default, with = "timestamp")]
+    pub latency_stddev: Duration,
+    #[serde(default, with = "timestamp")]
+    pub latency_min: Duration,
+    #[serde(default, with = "timestamp")]
+    pub latency_max: Duration,
+    #[serde(default, with = "timestamp")]
+    pub latency_ci95: Duration,
+    // Sampled operation latencies over all runs, for all operations and per
+    // operation kind.
+    #[serde(default, with = "timestamp")]
+    pub all_p50: Duration,
+    #[serde(default, with = "timestamp")]
+    pub all_p90: Duration,
+    #[serde(default, with = "timestamp")]
+    pub all_p99: Duration,
+    #[serde(default, with = "timestamp")]
+    pub all_p999: Duration,
+    #[serde(default, with = "timestamp")]
+    pub all_max: Duration,
+    #[serde(default, with = "timestamp")]
+    pub read_p50: Duration,
+    #[serde(default, with = "timestamp")]
+    pub read_p90: Duration,
+    #[serde(default, with = "timestamp")]
+    pub read_p99: Duration,
+    #[serde(default, with = "timestamp")]
+    pub read_p999: Duration,
+    #[serde(default, with = "timestamp")]
+    pub read_max: Duration,
+    #[serde(default, with = "timestamp")]
+    pub insert_p50: Duration,
+    #[serde(default, with = "timestamp")]
+    pub insert_p90: Duration,
+    #[serde(default, with = "timestamp")]
+    pub insert_p99: Duration,
+    #[serde(default, with = "timestamp")]
+    pub insert_p999: Duration,
+    #[serde(default, with = "timestamp")]
+    pub insert_max: Duration,
+    #[serde(default, with = "timestamp")]
+    pub remove_p50: Duration,
+    #[serde(default, with = "timestamp")]
+    pub remove_p90: Duration,
+    #[serde(default, with = "timestamp")]
+    pub remove_p99: Duration,
+    #[serde(default, with = "timestamp")]
+    pub remove_p999: Duration,
+    #[serde(default, with = "timestamp")]
+    pub remove_max: Duration,
+    #[serde(default, with = "timestamp")]
+    pub update_p50: Duration,
+    #[serde(default, with = "timestamp")]
+    pub update_p90: Duration,
+    #[serde(default, with = "timestamp")]
+    pub update_p99: Duration,
+    #[serde(default, with = "timestamp")]
+    pub update_p999: Duration,
+    #[serde(default, with = "timestamp")]
+    pub update_max: Duration,
+    #[serde(default, with = "timestamp")]
+    pub upsert_p50: Duration,
+    #[serde(default, with = "timestamp")]
+    pub upsert_p90: Duration,
+    #[serde(default, with = "timestamp")]
+    pub upsert_p99: Duration,
+    #[serde(default, with = "timestamp")]
+    pub upsert_p999: Duration,
+    #[serde(default, with = "timestamp")]
+    pub upsert_max: Duration,
+    // Per operation kind: operations per run, share of them the map reported
+    // as successful, throughput and mean sampled latency.
+    #[serde(default)]
+    pub read_count: u64,
+    #[serde(default)]
+    pub read_success_rate: f64,
+    #[serde(default)]
+    pub read_throughput: f64,
+    #[serde(default, with = "timestamp")]
+    pub read_mean: Duration,
+    #[serde(default)]
+    pub insert_count: u64,
+    #[serde(default)]
+    pub insert_success_rate: f64,
+    #[serde(default)]
+    pub insert_throughput: f64,
+    #[serde(default, with = "timestamp")]
+    pub insert_mean: Duration,
+    #[serde(default)]
+    pub remove_count: u64,
+    #[serde(default)]
+    pub remove_success_rate: f64,
+    #[serde(default)]
+    pub remove_throughput: f64,
+    #[serde(default, with = "timestamp")]
+    pub remove_mean: Duration,
+    #[serde(default)]
+    pub update_count: u64,
+    #[serde(default)]
+    pub update_success_rate: f64,
+    #[serde(default)]
+    pub update_throughput: f64,
+    #[serde(default, with = "timestamp")]
+    pub update_mean: Duration,
+    #[serde(default)]
+    pub upsert_count: u64,
+    #[serde(default)]
+    pub upsert_success_rate: f64,
+    #[serde(default)]
+    pub upsert_throughput: f64,
+    #[serde(default, with = "timestamp")]
+    pub upsert_mean: Duration,
+    #[serde(default)]
+    pub rss_before: u64,
+    #[serde(default)]
+    pub rss_prefilled: u64,
+    #[serde(default)]
+    pub rss_peak: u64,
+    #[serde(default)]
+    pub rss_end: u64,
+    #[serde(default)]
+    pub rss_dropped: u64,
+    #[serde(default)]
+    pub live_entries: u64,
+    /// Growth of the RSS over the run divided by `live_entries`.
+    #[serde(default)]
+    pub bytes_per_entry: f64,
+    /// Allocator calls per measured operation, only counted with the
+    /// `count-allocations` feature.
+    #[serde(default)]
+    pub allocations_per_op: f64,
+    #[serde(default)]
+    pub deallocations_per_op: f64,
+    /// Bytes requested from the allocator per measured operation.
+    #[serde(default)]
+    pub allocated_bytes_per_op: f64,
+    /// Jain's fairness index of the throughput of the threads, see
+    /// `stats::jain`, and the throughput of the slowest and fastest one.
+    #[serde(default)]
+    pub fairness: f64,
+    #[serde(default)]
+    pub thread_throughput_min: f64,
+    #[serde(default)]
+    pub thread_throughput_max: f64,
+    /// Number of keys all threads share with the `hot` distribution, 0 with
+    /// the others.
+    #[serde(default)]
+    pub hot_keys: u64,
+}
+
+impl Record {
+    /// An empty record holding the defaults of the columns that older result
+    /// files lack.
+    pub fn with_defaults() -> Record {
+        Record {
+            distribution: uniform(),
+            key_type: u64(),
+            value_type: u32(),
+            pin: none(),
+            status: ok(),
+            repetitions: one(),
+            ..Record::default()
+        }
+    }
+}
+
+fn one() -> u32 {
+    1
+}
+
+fn none() -> String {
+    "none".into()
+}
+
+fn ok() -> String {
+    "ok".into()
+}
+
+fn uniform() -> String {
+    "uniform".into()
+}
+
+fn u64() -> String {
+    "u64".into()
+}
+
+fn u32() -> String {
+    "u32".into()
 }
 
 mod timestamp {
@@ -0,0 +1,258 @@
+use std::collections::hash_map::RandomState;
+
+use crate::adapters::*;
+use crate::bench::{case, Handler, HasherKind, Options};
+use crate::keys::{BytesKey, KeyType, StringKey, U32Key, U64Key};
+use crate::state::RunState;
+use crate::values::{ArcValue, BoxValue, InlineValue, StringValue, ValueType};
+
+/// Runs every benchmark case of a single, fully instantiated map type.
+pub(crate) type Runner = fn(&str, &Options, &mut Handler, Option<&mut RunState>);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MapKind {
+    Ordered,
+    Hashed,
+}
+
+impl MapKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            MapKind::Ordered => "ordered",
+            MapKind::Hashed => "hashed",
+        }
+    }
+}
+
+pub struct Adapter {
+    pub name: &'static str,
+    pub kind: MapKind,
+    /// Hashers the map can be instantiated with. Maps that ignore the
+    /// `--hasher` option leave this empty and run under every hasher.
+    pub hashers: &'static [HasherKind],
+    /// Whether the map runs when no explicit `--maps` selection is given.
+    pub default: bool,
+    runner: fn(HasherKind, KeyType, ValueType) -> Runner,
+}
+
+impl Adapter {
+    pub fn supports(&self, hasher: HasherKind) -> bool {
+        self.hashers.is_empty() || self.hashers.contains(&hasher)
+    }
+
+    pub fn runner(&self, hasher: HasherKind, key: KeyType, value: ValueType) -> Runner {
+        (self.runner)(hasher, key, value)
+    }
+}
+
+/// Expands `$case` once per key type with `$k` naming the key.
+macro_rules! with_key {
+    ($key:expr, $k:ident => $case:expr) => {
+        match $key {
+            KeyType::U64 => {
+                type $k = U64Key;
+                $case
+            }
+            KeyType::U32 => {
+                type $k = U32Key;
+                $case
+            }
+            KeyType::String(_) => {
+                type $k = StringKey;
+                $case
+            }
+            KeyType::Bytes16 => {
+                type $k = BytesKey<16>;
+                $case
+            }
+            KeyType::Bytes32 => {
+                type $k = BytesKey<32>;
+                $case
+            }
+        }
+    };
+}
+
+/// Expands `$case` once per value type with `$v` naming the value.
+macro_rules! with_value {
+    ($value:expr, $v:ident => $case:expr) => {
+        match $value {
+            ValueType::U32 => {
+                type $v = u32;
+                $case
+            }
+            ValueType::Inline(16) => {
+                type $v = InlineValue<16>;
+                $case
+            }
+            ValueType::Inline(64) => {
+                type $v = InlineValue<64>;
+                $case
+            }
+            ValueType::Inline(256) => {
+                type $v = InlineValue<256>;
+                $case
+            }
+            ValueType::Inline(size) => unreachable!("inline values of {} bytes", size),
+            ValueType::Box(_) => {
+                type $v = BoxValue;
+                $case
+            }
+            ValueType::Arc(_) => {
+                type $v = ArcValue;
+                $case
+            }
+            ValueType::String(_) => {
+                type $v = StringValue;
+                $case
+            }
+        }
+    };
+}
+
+/// Expands `$case` for every combination of key and value type, which
+/// multiplies the maps and hashers compiled by 35 and release builds take
+/// several times longer.
+#[cfg(feature = "all-types")]
+macro_rules! with_types {
+    ($key:expr, $value:expr, $k:ident, $v:ident => $case:expr) => {
+        with_key!($key, $k => with_value!($value, $v => $case))
+    };
+}
+
+/// Expands `$case` for the key and value type. Only one of them may differ
+/// from the defaults unless built with the `all-types` feature, `validate`
+/// rejects the other combinations.
+#[cfg(not(feature = "all-types"))]
+macro_rules! with_types {
+    ($key:expr, $value:expr, $k:ident, $v:ident => $case:expr) => {
+        match ($key, $value) {
+            (key, ValueType::U32) => with_key!(key, $k => {
+                type $v = u32;
+                $case
+            }),
+            (KeyType::U64, value) => with_value!(value, $v => {
+                type $k = U64Key;
+                $case
+            }),
+            (key, value) => unreachable!("{} keys with {} values", key, value),
+        }
+    };
+}
+
+macro_rules! fixed_hasher {
+    ($table:ident) => {
+        |_, key, value| with_types!(key, value, K, V => case::<$table<K, V>>)
+    };
+}
+
+macro_rules! any_hasher {
+    ($table:ident) => {
+        |hasher, key, value| match hasher {
+            HasherKind::Std => with_types!(key, value, K, V => case::<$table<K, V, RandomState>>),
+            HasherKind::AHash => {
+                with_types!(key, value, K, V => case::<$table<K, V, ahash::RandomState>>)
+            }
+        }
+    };
+}
+
+pub static ADAPTERS: &[Adapter] = &[
+    Adapter {
+        name: "std:sync::RwLock<BTreeMap>",
+        kind: MapKind::Ordered,
+        hashers: &[],
+        default: false,
+        runner: fixed_hasher!(StdRwLockBTreeMapTable),
+    },
+    Adapter {
+        name: "parking_lot::RwLock<BTreeMap>",
+        kind: MapKind::Ordered,
+        hashers: &[],
+        default: false,
+        runner: fixed_hasher!(ParkingLotRwLockBTreeMapTable),
+    },
+    Adapter {
+        name: "CHashMap",
+        kind: MapKind::Hashed,
+        hashers: &[],
+        default: true,
+        runner: fixed_hasher!(CHashMapTable),
+    },
+    Adapter {
+        name: "CrossbeamSkipMap",
+        kind: MapKind::Ordered,
+        hashers: &[],
+        default: true,
+        runner: fixed_hasher!(CrossbeamSkipMapTable),
+    },
+    Adapter {
+        name: "std::sync::RwLock<StdHashMap>",
+        kind: MapKind::Hashed,
+        hashers: HasherKind::ALL,
+        default: false,
+        runner: any_hasher!(StdRwLockStdHashMapTable),
+    },
+    Adapter {
+        name: "parking_lot::RwLock<StdHashMap>",
+        kind: MapKind::Hashed,
+        hashers: HasherKind::ALL,
+        default: false,
+        runner: any_hasher!(ParkingLotRwLockStdHashMapTable),
+    },
+    Adapter {
+        name: "DashMap",
+        kind: MapKind::Hashed,
+        hashers: HasherKind::ALL,
+        default: true,
+        runner: any_hasher!(DashMapTable),
+    },
+    Adapter {
+        name: "Flurry",
+        kind: MapKind::Hashed,
+        hashers: HasherKind::ALL,
+        default: true,
+        runner: any_hasher!(FlurryTable),
+    },
+    Adapter {
+        name: "Evmap",
+        kind: MapKind::Hashed,
+        hashers: HasherKind::ALL,
+        default: true,
+        runner: any_hasher!(EvmapTable),
+    },
+    Adapter {
+        name: "Contrie",
+        kind: MapKind::Hashed,
+        hashers: HasherKind::ALL,
+        default: true,
+        runner: any_hasher!(ContrieTable),
+    },
+    Adapter {
+        name: "SccMap",
+        kind: MapKind::Hashed,
+        hashers: HasherKind::ALL,
+        default: true,
+        runner: any_hasher!(SccMapTable),
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Adapter> {
+    ADAPTERS.iter().find(|adapter| adapter.name == name)
+}
+
+/// Adapters to run for the given options, in registry order.
+///
+/// An explicit `--maps` selection overrides the per-adapter defaults.
+/// `--skip` is left to the caller so skipped maps still show up in the log.
+pub fn selected(options: &Options) -> impl Iterator<Item = &'static Adapter> + '_ {
+    ADAPTERS.iter().filter(move |adapter| {
+        let chosen = if options.maps.is_empty() {
+            adapter.default
+        } else {
+            options.maps.iter().any(|name| name == adapter.name)
+        };
+
+        chosen && adapter.supports(options.hasher)
+    })
+}
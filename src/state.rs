@@ -0,0 +1,156 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::{self, File, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use json::JsonValue;
+
+use crate::{
+    bench::{self, Options},
+    harness::Measurement,
+    record::Record,
+};
+
+/// Name of the run-state file in a `--resume` directory.
+const FILE: &str = "state.tsv";
+
+/// Progress of an interrupted run, kept in `<dir>/state.tsv`.
+///
+/// Every line starts with a `(workload, hasher, map, threads)` point, tab
+/// separated since map names contain commas and spaces, with the workload
+/// carrying its name and every setting that affects the record. The point is
+/// followed by the number of a finished repetition and its measurements as
+/// JSON, or by `done` once its record was written. Resuming skips the points
+/// that are done and measures only the missing repetitions of the others.
+pub(crate) struct RunState {
+    file: File,
+    done: HashSet<String>,
+    /// Measurements of every phase of the finished repetitions of points that
+    /// aren't done, by repetition.
+    repetitions: HashMap<String, BTreeMap<u32, Vec<Measurement>>>,
+}
+
+/// Identifies a point by the workload name and every option that affects its
+/// record, so that resuming with other settings measures it again.
+fn point(options: &Options, map: &str, threads: u32) -> String {
+    let settings = format!(
+        "{} operations={} repetitions={} latency_sample={} timeout_secs={} report_cold={}",
+        bench::header(options),
+        options.operations,
+        options.repetitions.max(1),
+        options.latency_sample,
+        options.timeout_secs,
+        options.report_cold,
+    );
+
+    format!(
+        "{}\t{}\t{}\t{}\t{}",
+        options.name.as_deref().unwrap_or(options.workload.name()),
+        settings,
+        options.hasher.name(),
+        map,
+        threads
+    )
+}
+
+/// Reads a repetition line after its point, `None` for lines cut short.
+fn repetition(line: &str) -> Option<(&str, u32, Vec<Measurement>)> {
+    let mut fields = line.rsplitn(3, '\t');
+    let (measurements, repetition, point) = (fields.next()?, fields.next()?, fields.next()?);
+
+    let measurements = json::parse(measurements)
+        .ok()?
+        .members()
+        .map(Measurement::from_json)
+        .collect::<Option<_>>()?;
+    Some((point, repetition.parse().ok()?, measurements))
+}
+
+impl RunState {
+    pub(crate) fn open(dir: &Path) -> RunState {
+        fs::create_dir_all(dir).expect("cannot create resume directory");
+        let path = dir.join(FILE);
+
+        let mut done = HashSet::new();
+        let mut repetitions: HashMap<_, BTreeMap<_, _>> = HashMap::new();
+        for line in fs::read_to_string(&path).unwrap_or_default().lines() {
+            if let Some(point) = line.strip_suffix("\tdone") {
+                done.insert(point.to_string());
+            } else if let Some((point, repetition, measurements)) = repetition(line) {
+                repetitions
+                    .entry(point.to_string())
+                    .or_default()
+                    .insert(repetition, measurements);
+            }
+        }
+        repetitions.retain(|point, _| !done.contains(point));
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .expect("cannot open run-state file");
+
+        RunState {
+            file,
+            done,
+            repetitions,
+        }
+    }
+
+    fn append(&mut self, line: String) {
+        // A single write, as child processes append to the same file.
+        self.file
+            .write_all(line.as_bytes())
+            .expect("cannot write run-state file");
+    }
+
+    pub(crate) fn is_done(&self, options: &Options, map: &str, threads: u32) -> bool {
+        self.done.contains(&point(options, map, threads))
+    }
+
+    /// Takes the repetitions of a point that were measured before.
+    pub(crate) fn repetitions(
+        &mut self,
+        options: &Options,
+        map: &str,
+        threads: u32,
+    ) -> BTreeMap<u32, Vec<Measurement>> {
+        self.repetitions
+            .remove(&point(options, map, threads))
+            .unwrap_or_default()
+    }
+
+    /// Keeps the measurements of a finished repetition of a point.
+    pub(crate) fn repeated(
+        &mut self,
+        options: &Options,
+        map: &str,
+        threads: u32,
+        repetition: u32,
+        measurements: &[Measurement],
+    ) {
+        let measurements: Vec<_> = measurements.iter().map(Measurement::to_json).collect();
+        self.append(format!(
+            "{}\t{}\t{}\n",
+            point(options, map, threads),
+            repetition,
+            JsonValue::from(measurements).dump()
+        ));
+    }
+
+    /// Marks the point of a written record done if it was the point's last
+    /// one. Timed out and crashed points are left open, so that resuming
+    /// measures them again.
+    pub(crate) fn recorded(&mut self, options: &Options, record: &Record) {
+        if record.status != "ok" || !bench::completes(options, record) {
+            return;
+        }
+
+        let point = point(options, &record.name, record.threads);
+        self.append(format!("{}\tdone\n", point));
+        self.done.insert(point);
+    }
+}
@@ -0,0 +1,66 @@
+/// Two-sided 95% quantiles of Student's t distribution for 1 to 30 degrees
+/// of freedom, larger samples use the normal approximation.
+static T_95: [f64; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Summary {
+    pub mean: f64,
+    pub median: f64,
+    /// Sample standard deviation.
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Half-width of the 95% confidence interval of the mean.
+    pub ci95: f64,
+}
+
+impl Summary {
+    pub fn of(samples: &[f64]) -> Self {
+        let n = samples.len();
+        assert!(n > 0, "no samples to summarize");
+
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).expect("NaN sample"));
+
+        let mean = sorted.iter().sum::<f64>() / n as f64;
+        let median = if n % 2 == 0 {
+            (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0
+        } else {
+            sorted[n / 2]
+        };
+
+        let (stddev, ci95) = if n > 1 {
+            let variance = sorted.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1) as f64;
+            let stddev = variance.sqrt();
+            let t = T_95.get(n - 2).copied().unwrap_or(1.96);
+            (stddev, t * stddev / (n as f64).sqrt())
+        } else {
+            (0.0, 0.0)
+        };
+
+        Self {
+            mean,
+            median,
+            stddev,
+            min: sorted[0],
+            max: sorted[n - 1],
+            ci95,
+        }
+    }
+}
+
+/// Jain's fairness index of `samples`: 1 if they are all equal, down to
+/// `1 / n` if a single one is non-zero.
+pub fn jain(samples: &[f64]) -> f64 {
+    let sum = samples.iter().sum::<f64>();
+    let squares = samples.iter().map(|x| x * x).sum::<f64>();
+
+    if squares == 0.0 {
+        return 0.0;
+    }
+    sum * sum / (samples.len() as f64 * squares)
+}
@@ -0,0 +1,371 @@
+use std::{fs, path::PathBuf, time::Duration};
+
+use json::JsonValue;
+use structopt::{clap, StructOpt};
+
+use crate::{
+    affinity::Pin,
+    bench::{self, parse_hasher_kind, HasherKind},
+    distribution::KeyDistribution,
+    format::Format,
+    keys::KeyType,
+    metadata,
+    phases::{self, Length, Phase},
+    registry,
+    state::RunState,
+    sweep::Sweep,
+    values::ValueType,
+    workloads::{MixOptions, WorkloadKind},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct Options {
+    /// JSON file describing the run matrix, see `suites/default.json`.
+    file: PathBuf,
+}
+
+struct Workload {
+    /// Base name of the result files, `<name>.<hasher>.csv`.
+    name: String,
+    kind: WorkloadKind,
+    mix: MixOptions,
+    distribution: KeyDistribution,
+    key_type: KeyType,
+    value_type: ValueType,
+    phases: Vec<Phase>,
+}
+
+struct Suite {
+    output: PathBuf,
+    workloads: Vec<Workload>,
+    hashers: Vec<HasherKind>,
+    threads: Option<Vec<u32>>,
+    sweep: Sweep,
+    oversubscribe: bool,
+    pin: Pin,
+    maps: Vec<String>,
+    skip: Vec<String>,
+    operations: f64,
+    duration: Option<Duration>,
+    warmup: Option<Length>,
+    report_cold: bool,
+    repetitions: u32,
+    latency_sample: u32,
+    gc_sleep_ms: u64,
+    timeout_secs: u64,
+}
+
+impl Suite {
+    fn options(&self, workload: &Workload, hasher: HasherKind) -> bench::Options {
+        bench::Options {
+            workload: workload.kind,
+            mix: workload.mix.clone(),
+            distribution: workload.distribution,
+            key_type: workload.key_type,
+            value_type: workload.value_type,
+            operations: self.operations,
+            duration: self.duration,
+            warmup: self.warmup,
+            report_cold: self.report_cold,
+            phase: workload.phases.clone(),
+            pin: self.pin.clone(),
+            threads: self.threads.clone(),
+            sweep: self.sweep,
+            oversubscribe: self.oversubscribe,
+            hasher,
+            gc_sleep_ms: self.gc_sleep_ms,
+            timeout_secs: self.timeout_secs,
+            repetitions: self.repetitions,
+            latency_sample: self.latency_sample,
+            maps: self.maps.clone(),
+            skip: self.skip.clone(),
+            format: Format::Csv,
+            output: None,
+            append: false,
+            resume: Some(self.output.clone()),
+            name: Some(workload.name.clone()),
+            in_process: false,
+            child: None,
+        }
+    }
+
+    /// File the records of `workload` hashed with `hasher` are appended to.
+    fn results(&self, workload: &Workload, hasher: HasherKind) -> PathBuf {
+        self.output
+            .join(format!("{}.{}.csv", workload.name, hasher.name()))
+    }
+}
+
+const SUITE_KEYS: &[&str] = &[
+    "output",
+    "workloads",
+    "hashers",
+    "threads",
+    "sweep",
+    "oversubscribe",
+    "pin",
+    "maps",
+    "skip",
+    "operations",
+    "duration",
+    "warmup",
+    "report_cold",
+    "repetitions",
+    "latency_sample",
+    "gc_sleep_ms",
+    "timeout_secs",
+];
+
+const WORKLOAD_KEYS: &[&str] = &[
+    "name",
+    "workload",
+    "read",
+    "insert",
+    "remove",
+    "update",
+    "upsert",
+    "initial_capacity_log2",
+    "prefill_fraction",
+    "distribution",
+    "key_type",
+    "value_type",
+    "phases",
+];
+
+fn check_keys(value: &JsonValue, allowed: &[&str], context: &str) -> Result<(), String> {
+    match value.entries().find(|(key, _)| !allowed.contains(key)) {
+        Some((key, _)) => Err(format!("unknown key '{}' in {}", key, context)),
+        None => Ok(()),
+    }
+}
+
+fn field<'a, T>(
+    value: &'a JsonValue,
+    key: &str,
+    get: fn(&'a JsonValue) -> Option<T>,
+) -> Result<Option<T>, String> {
+    let field = &value[key];
+
+    if field.is_null() {
+        return Ok(None);
+    }
+
+    get(field)
+        .map(Some)
+        .ok_or_else(|| format!("invalid value for '{}'", key))
+}
+
+fn strings(value: &JsonValue, key: &str) -> Result<Vec<String>, String> {
+    let field = &value[key];
+
+    if field.is_null() {
+        return Ok(Vec::new());
+    }
+
+    if !field.is_array() {
+        return Err(format!("'{}' must be an array", key));
+    }
+
+    field
+        .members()
+        .map(|member| {
+            member
+                .as_str()
+                .map(String::from)
+                .ok_or_else(|| format!("'{}' must only contain strings", key))
+        })
+        .collect()
+}
+
+fn map_names(value: &JsonValue, key: &str) -> Result<Vec<String>, String> {
+    let names = strings(value, key)?;
+
+    match names.iter().find(|name| registry::find(name).is_none()) {
+        Some(name) => Err(format!(
+            "unknown map '{}' in '{}', see `conc-map-bench list` for valid names",
+            name, key
+        )),
+        None => Ok(names),
+    }
+}
+
+fn parse_workload(value: &JsonValue) -> Result<Workload, String> {
+    if let Some(name) = value.as_str() {
+        let kind = name.parse()?;
+        return Ok(Workload {
+            name: name.into(),
+            kind,
+            mix: MixOptions::default(),
+            distribution: KeyDistribution::Uniform,
+            key_type: KeyType::U64,
+            value_type: ValueType::U32,
+            phases: Vec::new(),
+        });
+    }
+
+    if !value.is_object() {
+        return Err("workloads must be names or objects".into());
+    }
+
+    check_keys(value, WORKLOAD_KEYS, "workload")?;
+
+    let kind: WorkloadKind = field(value, "workload", JsonValue::as_str)?
+        .ok_or("workload object without 'workload' key")?
+        .parse()?;
+
+    let mix = MixOptions {
+        read: field(value, "read", JsonValue::as_u8)?,
+        insert: field(value, "insert", JsonValue::as_u8)?,
+        remove: field(value, "remove", JsonValue::as_u8)?,
+        update: field(value, "update", JsonValue::as_u8)?,
+        upsert: field(value, "upsert", JsonValue::as_u8)?,
+        initial_capacity_log2: field(value, "initial_capacity_log2", JsonValue::as_u8)?,
+        prefill_fraction: field(value, "prefill_fraction", JsonValue::as_f64)?,
+    };
+
+    let distribution = match field(value, "distribution", JsonValue::as_str)? {
+        Some(distribution) => distribution.parse()?,
+        None => KeyDistribution::Uniform,
+    };
+
+    let key_type = match field(value, "key_type", JsonValue::as_str)? {
+        Some(key_type) => key_type.parse()?,
+        None => KeyType::U64,
+    };
+
+    let value_type = match field(value, "value_type", JsonValue::as_str)? {
+        Some(value_type) => value_type.parse()?,
+        None => ValueType::U32,
+    };
+
+    let phases = strings(value, "phases")?
+        .iter()
+        .map(|phase| phase.parse())
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let name = field(value, "name", JsonValue::as_str)?
+        .unwrap_or_else(|| kind.name())
+        .into();
+
+    Ok(Workload {
+        name,
+        kind,
+        mix,
+        distribution,
+        key_type,
+        value_type,
+        phases,
+    })
+}
+
+fn parse(text: &str) -> Result<Suite, String> {
+    let root = json::parse(text).map_err(|e| e.to_string())?;
+
+    if !root.is_object() {
+        return Err("suite must be a JSON object".into());
+    }
+
+    check_keys(&root, SUITE_KEYS, "suite")?;
+
+    let workloads = root["workloads"]
+        .members()
+        .map(parse_workload)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let hashers = strings(&root, "hashers")?
+        .iter()
+        .map(|hasher| parse_hasher_kind(hasher).map_err(String::from))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if workloads.is_empty() || hashers.is_empty() {
+        return Err("suite needs at least one workload and one hasher".into());
+    }
+
+    for (i, workload) in workloads.iter().enumerate() {
+        if workloads[..i]
+            .iter()
+            .any(|other| other.name == workload.name)
+        {
+            return Err(format!("duplicate workload name '{}'", workload.name));
+        }
+    }
+
+    let threads = if root["threads"].is_null() {
+        None
+    } else {
+        let threads = root["threads"]
+            .members()
+            .map(|n| n.as_u32().filter(|&n| n > 0))
+            .collect::<Option<Vec<_>>>()
+            .filter(|threads| !threads.is_empty())
+            .ok_or("'threads' must be a non-empty array of positive integers")?;
+        Some(threads)
+    };
+
+    let suite = Suite {
+        output: field(&root, "output", JsonValue::as_str)?
+            .unwrap_or("results")
+            .into(),
+        workloads,
+        hashers,
+        threads,
+        sweep: match field(&root, "sweep", JsonValue::as_str)? {
+            Some(sweep) => sweep.parse()?,
+            None => Sweep::Linear(None),
+        },
+        oversubscribe: field(&root, "oversubscribe", JsonValue::as_bool)?.unwrap_or(false),
+        pin: match field(&root, "pin", JsonValue::as_str)? {
+            Some(pin) => pin.parse()?,
+            None => Pin::None,
+        },
+        maps: map_names(&root, "maps")?,
+        skip: map_names(&root, "skip")?,
+        operations: field(&root, "operations", JsonValue::as_f64)?.unwrap_or(1.0),
+        duration: field(&root, "duration", JsonValue::as_str)?
+            .map(phases::parse_time)
+            .transpose()?,
+        warmup: field(&root, "warmup", JsonValue::as_str)?
+            .map(str::parse)
+            .transpose()?,
+        report_cold: field(&root, "report_cold", JsonValue::as_bool)?.unwrap_or(false),
+        repetitions: field(&root, "repetitions", JsonValue::as_u32)?.unwrap_or(1),
+        latency_sample: field(&root, "latency_sample", JsonValue::as_u32)?.unwrap_or(8),
+        gc_sleep_ms: field(&root, "gc_sleep_ms", JsonValue::as_u64)?.unwrap_or(2000),
+        timeout_secs: field(&root, "timeout_secs", JsonValue::as_u64)?.unwrap_or(0),
+    };
+
+    for hasher in &suite.hashers {
+        for workload in &suite.workloads {
+            bench::validate(&suite.options(workload, *hasher))
+                .and_then(|()| bench::check_columns(&suite.results(workload, *hasher)))
+                .map_err(|message| format!("workload '{}': {}", workload.name, message))?;
+        }
+    }
+
+    Ok(suite)
+}
+
+fn run(suite: &Suite, workload: &Workload, hasher: HasherKind, state: &mut RunState) {
+    let path = suite.results(workload, hasher);
+    let options = suite.options(workload, hasher);
+    metadata::write(&path, &options, true);
+    bench::run(&options, &mut bench::append_handler(&path), Some(state));
+}
+
+pub fn suite(options: &Options) {
+    let suite = fs::read_to_string(&options.file)
+        .map_err(|e| format!("cannot read {}: {}", options.file.display(), e))
+        .and_then(|text| parse(&text))
+        .unwrap_or_else(|message| {
+            clap::Error::with_description(&message, clap::ErrorKind::ValueValidation).exit()
+        });
+
+    fs::create_dir_all(&suite.output).expect("cannot create output directory");
+    let mut state = RunState::open(&suite.output);
+
+    for hasher in &suite.hashers {
+        for workload in &suite.workloads {
+            run(&suite, workload, *hasher, &mut state);
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, Read},
+    path::Path,
+    process::{self, Child, Command, ExitStatus, Stdio},
+    thread::sleep,
+    time::Duration,
+};
+
+use crate::{
+    bench::{self, Handler, Options},
+    record::Record,
+    state::RunState,
+};
+
+/// How often the records file of a running child is read.
+const POLL: Duration = Duration::from_millis(100);
+
+/// Passes every complete record the running `child` writes to `path` on to
+/// `f` as it comes in, until the child exits. A record cut short by a crash
+/// misses its line end and is left out, and so is everything after a record
+/// that fails to parse. Record fields hold no line breaks.
+fn follow(child: &mut Child, path: &Path, mut f: impl FnMut(Record)) -> io::Result<ExitStatus> {
+    let mut file = File::open(path)?;
+    let mut pending = Vec::new();
+    let mut headers = None;
+    let mut broken = false;
+
+    loop {
+        // Checked before reading, so that everything the child wrote before
+        // exiting is read.
+        let status = child.try_wait()?;
+        file.read_to_end(&mut pending)?;
+
+        let complete = pending
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |i| i + 1);
+        let lines: Vec<u8> = pending.drain(..complete).collect();
+        let mut rd = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(lines.as_slice());
+
+        for row in rd.records() {
+            if broken {
+                break;
+            }
+            let row = match row {
+                Ok(row) => row,
+                Err(_) => {
+                    broken = true;
+                    break;
+                }
+            };
+            match &headers {
+                None => headers = Some(row),
+                Some(headers) => match row.deserialize::<Record>(Some(headers)) {
+                    Ok(record) => f(record),
+                    Err(_) => broken = true,
+                },
+            }
+        }
+
+        if let Some(status) = status {
+            return Ok(status);
+        }
+        sleep(POLL);
+    }
+}
+
+/// Runs map `name` in child processes, one `bench --child` invocation at a
+/// time, passing the records they write to their records file on to
+/// `handler` and marking their points done in `state`. The children's stdout
+/// is left to their messages.
+///
+/// A child that dies takes the thread count it was measuring with it: that
+/// point is recorded as crashed and a fresh child continues with the rest,
+/// just like after a child exits because of a timeout.
+pub(crate) fn case(
+    name: &str,
+    options: &Options,
+    handler: &mut Handler,
+    mut state: Option<&mut RunState>,
+) {
+    let exe = env::current_exe().expect("cannot locate own executable");
+    let records = env::temp_dir().join(format!("conc-map-bench-{}.csv", process::id()));
+    let mut remaining = bench::thread_counts(options);
+
+    while !remaining.is_empty() {
+        let mut child_options = options.clone();
+        child_options.maps = vec![name.into()];
+        child_options.skip.clear();
+        child_options.threads = Some(remaining.clone());
+        child_options.output = None;
+        child_options.append = false;
+        child_options.child = Some(records.clone());
+
+        File::create(&records).expect("cannot create records file");
+        let mut child = Command::new(&exe)
+            .arg("bench")
+            .args(child_options.to_args())
+            .stdin(Stdio::null())
+            .spawn()
+            .expect("cannot spawn child process");
+
+        let mut recorded = false;
+        let status = follow(&mut child, &records, |record| {
+            if bench::completes(options, &record) {
+                remaining.retain(|&n| n != record.threads);
+            }
+            recorded = true;
+            handler(&record);
+            if let Some(state) = state.as_deref_mut() {
+                state.recorded(options, &record);
+            }
+        })
+        .expect("cannot follow child process");
+
+        // A child leaves early on purpose after recording a timed out run.
+        if recorded && (status.success() || status.code() == Some(bench::TIMED_OUT)) {
+            continue;
+        }
+
+        if let Some(&threads) = remaining.first() {
+            let status = if status.success() {
+                "crashed: exited without a record".into()
+            } else {
+                format!("crashed: {}", status)
+            };
+            handler(&bench::failed(name, options, threads, status));
+            remaining.remove(0);
+        }
+    }
+
+    let _ = fs::remove_file(&records);
+}
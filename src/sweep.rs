@@ -0,0 +1,160 @@
+use std::{collections::BTreeSet, fmt, fs, path::Path, str::FromStr};
+
+use crate::affinity;
+
+/// How the thread counts of a run are picked when `--threads` isn't given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sweep {
+    /// Every `step`-th thread count, the step growing with the machine when
+    /// none is given.
+    Linear(Option<u32>),
+    /// Powers of two.
+    Geometric,
+}
+
+impl fmt::Display for Sweep {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Sweep::Linear(None) => write!(f, "linear"),
+            Sweep::Linear(Some(step)) => write!(f, "linear:{}", step),
+            Sweep::Geometric => write!(f, "geometric"),
+        }
+    }
+}
+
+impl FromStr for Sweep {
+    type Err = String;
+
+    /// Parses `linear[:step]` and `geometric`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("linear", step)) => match step.parse() {
+                Ok(step) if step > 0 => Ok(Sweep::Linear(Some(step))),
+                _ => Err(format!("invalid sweep step '{}'", step)),
+            },
+            None if s == "linear" => Ok(Sweep::Linear(None)),
+            None if s == "geometric" => Ok(Sweep::Geometric),
+            _ => Err(format!(
+                "unknown sweep '{}', must be one of 'linear[:step]' or 'geometric'",
+                s
+            )),
+        }
+    }
+}
+
+/// CPUs a run can actually use.
+#[derive(Debug, Clone, Copy)]
+pub struct Budget {
+    /// Logical CPUs in the affinity mask, capped by the cgroup quota.
+    pub logical: u32,
+    /// Physical cores among them.
+    pub physical: u32,
+    /// CPU quota of the cgroup, `None` if unlimited.
+    pub quota: Option<f64>,
+}
+
+/// Quota of a cgroup v2 `cpu.max` or v1 `cpu.cfs_quota_us`/`cpu.cfs_period_us`
+/// in CPUs.
+fn read_quota(dir: &Path) -> Option<f64> {
+    if let Ok(max) = fs::read_to_string(dir.join("cpu.max")) {
+        let (quota, period) = max.trim().split_once(' ')?;
+        return Some(quota.parse::<f64>().ok()? / period.parse::<f64>().ok()?);
+    }
+
+    let quota: f64 = fs::read_to_string(dir.join("cpu.cfs_quota_us"))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    let period: f64 = fs::read_to_string(dir.join("cpu.cfs_period_us"))
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+    (quota > 0.0).then_some(quota / period)
+}
+
+/// Smallest CPU quota of the cgroups this process is in and their ancestors.
+fn cgroup_quota() -> Option<f64> {
+    let cgroups = fs::read_to_string("/proc/self/cgroup").ok()?;
+    let mut dirs = Vec::new();
+
+    for line in cgroups.lines() {
+        let mut fields = line.splitn(3, ':');
+        let (controllers, path) = match (fields.next(), fields.next(), fields.next()) {
+            (Some(_), Some(controllers), Some(path)) => (controllers, path),
+            _ => continue,
+        };
+        let root = if controllers.is_empty() {
+            Path::new("/sys/fs/cgroup").to_path_buf()
+        } else if controllers.split(',').any(|c| c == "cpu") {
+            Path::new("/sys/fs/cgroup").join(controllers)
+        } else {
+            continue;
+        };
+
+        // Inside a container the path may name a cgroup that is mounted as
+        // the root, so the root itself is checked as well.
+        let mut dir = root.join(path.trim_start_matches('/'));
+        while dir.starts_with(&root) {
+            dirs.push(dir.clone());
+            if !dir.pop() {
+                break;
+            }
+        }
+    }
+
+    dirs.iter()
+        .filter_map(|dir| read_quota(dir))
+        .min_by(|a, b| a.total_cmp(b))
+}
+
+pub fn budget() -> Budget {
+    let cpus = affinity::topology();
+    let quota = cgroup_quota();
+
+    let limit = match quota {
+        Some(quota) => (quota.ceil() as u32).max(1),
+        None => u32::MAX,
+    };
+    let logical = (cpus.len() as u32).min(limit).max(1);
+    let cores: BTreeSet<_> = cpus.iter().map(|cpu| (cpu.package, cpu.core)).collect();
+
+    Budget {
+        logical,
+        physical: (cores.len() as u32).min(logical).max(1),
+        quota,
+    }
+}
+
+/// Thread counts of `sweep` up to the CPU budget, with the number of physical
+/// cores and the full budget always included, and twice and four times the
+/// budget with `oversubscribe`.
+pub fn thread_counts(sweep: Sweep, oversubscribe: bool) -> Vec<u32> {
+    let Budget {
+        logical, physical, ..
+    } = budget();
+
+    let mut counts: BTreeSet<u32> = match sweep {
+        Sweep::Geometric => (0..32)
+            .map(|shift| 1 << shift)
+            .take_while(|&n| n <= logical)
+            .collect(),
+        Sweep::Linear(step) => {
+            let step = match (step, logical) {
+                (Some(step), _) => step,
+                (None, 0..=10) => 1,
+                (None, 11..=16) => 2,
+                (None, _) => 4,
+            };
+            (0..=logical).step_by(step as usize).skip(1).collect()
+        }
+    };
+
+    counts.extend([1, physical, logical]);
+    if oversubscribe {
+        counts.extend([2 * logical, 4 * logical]);
+    }
+
+    counts.into_iter().collect()
+}
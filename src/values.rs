@@ -0,0 +1,205 @@
+use std::{
+    fmt,
+    hash::Hash,
+    mem,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
+
+/// Values stored in the maps.
+pub trait Value: Default + Clone + Eq + Hash + Send + Sync + 'static {
+    /// Mutates the value in place, as an `update` does.
+    fn update(&mut self);
+
+    /// The value after an update, for maps that only hand out shared
+    /// references and replace values on update.
+    fn updated(&self) -> Self {
+        let mut value = self.clone();
+        value.update();
+        value
+    }
+}
+
+impl Value for u32 {
+    fn update(&mut self) {
+        *self = self.wrapping_add(1);
+    }
+}
+
+/// Value type the maps are instantiated with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValueType {
+    #[default]
+    U32,
+    /// `[u8; N]` stored inline, `N` is one of `INLINE_SIZES`.
+    Inline(usize),
+    /// `Box<[u8]>` of the given length.
+    Box(usize),
+    /// `Arc<[u8]>` of the given length, replaced on update.
+    Arc(usize),
+    /// `String` of the given length.
+    String(usize),
+}
+
+/// Sizes of inline values, each one is a separate instantiation of every map.
+pub const INLINE_SIZES: &[usize] = &[16, 64, 256];
+
+const DEFAULT_HEAP_LEN: usize = 64;
+const MAX_HEAP_LEN: usize = 1 << 20;
+
+impl ValueType {
+    /// Length of heap-allocated values.
+    pub fn heap_len(self) -> Option<usize> {
+        match self {
+            ValueType::Box(len) | ValueType::Arc(len) | ValueType::String(len) => Some(len),
+            ValueType::U32 | ValueType::Inline(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for ValueType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValueType::U32 => write!(f, "u32"),
+            ValueType::Inline(size) => write!(f, "inline:{}", size),
+            ValueType::Box(len) => write!(f, "box:{}", len),
+            ValueType::Arc(len) => write!(f, "arc:{}", len),
+            ValueType::String(len) => write!(f, "string:{}", len),
+        }
+    }
+}
+
+fn parse_heap_len(len: Option<&str>) -> Result<usize, String> {
+    match len.map(str::parse) {
+        None => Ok(DEFAULT_HEAP_LEN),
+        Some(Ok(len)) if (1..=MAX_HEAP_LEN).contains(&len) => Ok(len),
+        Some(_) => Err(format!(
+            "value length must be between 1 and {}",
+            MAX_HEAP_LEN
+        )),
+    }
+}
+
+impl FromStr for ValueType {
+    type Err = String;
+
+    /// Parses `u32`, `inline:<size>`, `box[:len]`, `arc[:len]` and
+    /// `string[:len]`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, param) = match s.split_once(':') {
+            Some((name, param)) => (name, Some(param)),
+            None => (s, None),
+        };
+
+        match (name, param) {
+            ("u32", None) => Ok(ValueType::U32),
+            ("inline", Some(size)) => match size.parse() {
+                Ok(size) if INLINE_SIZES.contains(&size) => Ok(ValueType::Inline(size)),
+                Ok(size) => Err(format!(
+                    "inline values of {} bytes aren't supported, the size must be one of {:?}",
+                    size, INLINE_SIZES
+                )),
+                Err(_) => Err(format!("invalid inline value size '{}'", size)),
+            },
+            ("box", len) => parse_heap_len(len).map(ValueType::Box),
+            ("arc", len) => parse_heap_len(len).map(ValueType::Arc),
+            ("string", len) => parse_heap_len(len).map(ValueType::String),
+            _ => Err(format!(
+                "unknown value type '{}', must be one of u32, inline:<size>, box[:len], \
+                 arc[:len] or string[:len]",
+                s
+            )),
+        }
+    }
+}
+
+/// Length of heap-allocated values, set once per run like the string key
+/// length in `crate::keys`.
+static HEAP_LEN: AtomicUsize = AtomicUsize::new(DEFAULT_HEAP_LEN);
+
+pub fn set_heap_len(len: usize) {
+    HEAP_LEN.store(len, Ordering::Relaxed);
+}
+
+fn heap_len() -> usize {
+    HEAP_LEN.load(Ordering::Relaxed)
+}
+
+/// Writes every byte, so updates cost in proportion to the value size.
+fn bump(bytes: &mut [u8]) {
+    for byte in bytes {
+        *byte = byte.wrapping_add(1);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct InlineValue<const N: usize>(pub(crate) [u8; N]);
+
+impl<const N: usize> Default for InlineValue<N> {
+    fn default() -> Self {
+        Self([0; N])
+    }
+}
+
+impl<const N: usize> Value for InlineValue<N> {
+    fn update(&mut self) {
+        bump(&mut self.0);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BoxValue(pub(crate) Box<[u8]>);
+
+impl Default for BoxValue {
+    fn default() -> Self {
+        Self(vec![0; heap_len()].into_boxed_slice())
+    }
+}
+
+impl Value for BoxValue {
+    fn update(&mut self) {
+        bump(&mut self.0);
+    }
+}
+
+/// Shared and immutable, so an update allocates a new value and drops the
+/// old one once the last reader lets go of it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ArcValue(pub(crate) Arc<[u8]>);
+
+impl Default for ArcValue {
+    fn default() -> Self {
+        Self(vec![0; heap_len()].into())
+    }
+}
+
+impl Value for ArcValue {
+    fn update(&mut self) {
+        let mut bytes = self.0.to_vec();
+        bump(&mut bytes);
+        self.0 = bytes.into();
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StringValue(pub(crate) String);
+
+impl Default for StringValue {
+    fn default() -> Self {
+        Self("a".repeat(heap_len()))
+    }
+}
+
+impl Value for StringValue {
+    fn update(&mut self) {
+        // Rotate through the lowercase letters, keeping the string valid UTF-8.
+        let mut bytes = mem::take(&mut self.0).into_bytes();
+        for byte in &mut bytes {
+            *byte = if *byte == b'z' { b'a' } else { *byte + 1 };
+        }
+        self.0 = String::from_utf8(bytes).expect("lowercase letters");
+    }
+}
@@ -1,14 +1,52 @@
-use std::{fmt::Debug, str::FromStr};
+use std::{fmt::Debug, str::FromStr, time::Duration};
 
-use bustle::*;
+use bustle::Mix;
+use structopt::StructOpt;
 
-use super::bench::Options;
+use super::affinity;
+use super::bench::{self, Options};
+use super::distribution::KeyDistribution;
+use super::harness::{self, Workload};
+use super::keys::KeyType;
+use super::phases::{Length, Phase};
+use super::values::ValueType;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum WorkloadKind {
     ReadHeavy,
     Exchange,
     RapidGrow,
+    Aggregate,
+    GetOrInsert,
+    HotKeys,
+    Phased,
+    Custom,
+}
+
+impl WorkloadKind {
+    pub const ALL: &'static [WorkloadKind] = &[
+        WorkloadKind::ReadHeavy,
+        WorkloadKind::Exchange,
+        WorkloadKind::RapidGrow,
+        WorkloadKind::Aggregate,
+        WorkloadKind::GetOrInsert,
+        WorkloadKind::HotKeys,
+        WorkloadKind::Phased,
+        WorkloadKind::Custom,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            WorkloadKind::ReadHeavy => "ReadHeavy",
+            WorkloadKind::Exchange => "Exchange",
+            WorkloadKind::RapidGrow => "RapidGrow",
+            WorkloadKind::Aggregate => "Aggregate",
+            WorkloadKind::GetOrInsert => "GetOrInsert",
+            WorkloadKind::HotKeys => "HotKeys",
+            WorkloadKind::Phased => "Phased",
+            WorkloadKind::Custom => "Custom",
+        }
+    }
 }
 
 impl FromStr for WorkloadKind {
@@ -19,11 +57,271 @@ impl FromStr for WorkloadKind {
             "ReadHeavy" => Ok(Self::ReadHeavy),
             "Exchange" => Ok(Self::Exchange),
             "RapidGrow" => Ok(Self::RapidGrow),
+            "Aggregate" => Ok(Self::Aggregate),
+            "GetOrInsert" => Ok(Self::GetOrInsert),
+            "HotKeys" => Ok(Self::HotKeys),
+            "Phased" => Ok(Self::Phased),
+            "Custom" => Ok(Self::Custom),
             _ => Err("unknown workload"),
         }
     }
 }
 
+/// Operation mix of the `Custom` workload. Percentages that are not given
+/// default to zero and must add up to 100.
+#[derive(Debug, Clone, Default, StructOpt)]
+pub struct MixOptions {
+    #[structopt(long)]
+    pub read: Option<u8>,
+    #[structopt(long)]
+    pub insert: Option<u8>,
+    #[structopt(long)]
+    pub remove: Option<u8>,
+    #[structopt(long)]
+    pub update: Option<u8>,
+    #[structopt(long)]
+    pub upsert: Option<u8>,
+    #[structopt(long)]
+    pub initial_capacity_log2: Option<u8>,
+    #[structopt(long)]
+    pub prefill_fraction: Option<f64>,
+}
+
+impl MixOptions {
+    fn is_empty(&self) -> bool {
+        self.read.is_none()
+            && self.insert.is_none()
+            && self.remove.is_none()
+            && self.update.is_none()
+            && self.upsert.is_none()
+            && self.initial_capacity_log2.is_none()
+            && self.prefill_fraction.is_none()
+    }
+
+    /// Command line arguments of the options that are set.
+    pub(crate) fn to_args(&self) -> Vec<String> {
+        let fields = [
+            ("--read", self.read.map(|v| v.to_string())),
+            ("--insert", self.insert.map(|v| v.to_string())),
+            ("--remove", self.remove.map(|v| v.to_string())),
+            ("--update", self.update.map(|v| v.to_string())),
+            ("--upsert", self.upsert.map(|v| v.to_string())),
+            (
+                "--initial-capacity-log2",
+                self.initial_capacity_log2.map(|v| v.to_string()),
+            ),
+            (
+                "--prefill-fraction",
+                self.prefill_fraction.map(|v| v.to_string()),
+            ),
+        ];
+
+        fields
+            .iter()
+            .filter_map(|(flag, value)| Some([flag.to_string(), value.clone()?]))
+            .flatten()
+            .collect()
+    }
+
+    fn mix(&self) -> Mix {
+        Mix {
+            read: self.read.unwrap_or(0),
+            insert: self.insert.unwrap_or(0),
+            remove: self.remove.unwrap_or(0),
+            update: self.update.unwrap_or(0),
+            upsert: self.upsert.unwrap_or(0),
+        }
+    }
+
+    fn initial_capacity_log2(&self) -> u8 {
+        self.initial_capacity_log2.unwrap_or(25)
+    }
+
+    fn prefill_fraction(&self) -> f64 {
+        self.prefill_fraction.unwrap_or(0.0)
+    }
+}
+
+/// Checks that the mix options are consistent with the selected workload.
+pub(crate) fn validate(options: &Options) -> Result<(), String> {
+    let custom = &options.mix;
+
+    affinity::validate(&options.pin)?;
+
+    if matches!(options.workload, WorkloadKind::HotKeys)
+        && !matches!(distribution(options), KeyDistribution::Hot(_))
+    {
+        return Err("the HotKeys workload needs a hot[:keys] distribution".into());
+    }
+
+    if !options.phase.is_empty() && !matches!(options.workload, WorkloadKind::Phased) {
+        return Err("phases are only valid with `--workload Phased`".into());
+    }
+
+    if !cfg!(feature = "all-types")
+        && options.key_type != KeyType::U64
+        && options.value_type != ValueType::U32
+    {
+        return Err(
+            "only one of the key and value type can differ from u64 keys and u32 values, \
+             build with the `all-types` feature to combine them"
+                .into(),
+        );
+    }
+
+    if options.report_cold && options.warmup.is_none() {
+        return Err("reporting the cold map needs a warm-up".into());
+    }
+
+    if options.duration.is_some() && matches!(options.workload, WorkloadKind::Phased) {
+        return Err("give the Phased workload time lengths instead of `--duration`".into());
+    }
+
+    let phases = phases(options);
+    for (i, phase) in phases.iter().enumerate() {
+        if phase.name == harness::WARMUP {
+            return Err(format!("phase name '{}' is reserved", phase.name));
+        }
+        if phases[..i].iter().any(|other| other.name == phase.name) {
+            return Err(format!("phase name '{}' is used twice", phase.name));
+        }
+    }
+
+    if matches!(options.workload, WorkloadKind::Custom) {
+        validate_custom(custom)?;
+    } else if !custom.is_empty() {
+        return Err("mix options are only valid with `--workload Custom`".into());
+    }
+
+    // Only `HotKeys` keeps the hot keys present, other mixes would remove
+    // them and leave the threads fighting over absent keys.
+    if matches!(distribution(options), KeyDistribution::Hot(_)) && create(options, 1).removes() {
+        return Err("the hot[:keys] distribution needs a mix without removes".into());
+    }
+
+    // Ids are distinct in their low 32 bits only below 2^32, colliding keys
+    // would fail the harness's checks of what the map reports.
+    if options.key_type == KeyType::U32 {
+        for threads in bench::thread_counts(options) {
+            match create(options, threads).key_ids() {
+                None => {
+                    return Err(
+                        "u32 keys run out when inserting for a time, run for a number of \
+                         operations instead of `--duration` or time lengths"
+                            .into(),
+                    )
+                }
+                Some(ids) if ids > 1 << 32 => {
+                    return Err(format!(
+                        "{} threads need {} distinct keys, more than u32 keys have, lower \
+                         `--operations` or the initial capacity",
+                        threads, ids
+                    ))
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Largest `--initial-capacity-log2`, a trillion entries is beyond any
+/// machine the benchmark runs on and larger capacities overflow the key
+/// bookkeeping.
+const MAX_CAPACITY_LOG2: u8 = 40;
+
+fn validate_custom(custom: &MixOptions) -> Result<(), String> {
+    let mix = custom.mix();
+    let total: u32 = [mix.read, mix.insert, mix.remove, mix.update, mix.upsert]
+        .iter()
+        .map(|&p| u32::from(p))
+        .sum();
+
+    if total != 100 {
+        return Err(format!("mix percentages add up to {}, not 100", total));
+    }
+
+    if !(0.0..=1.0).contains(&custom.prefill_fraction()) {
+        return Err("prefill fraction must be between 0 and 1".into());
+    }
+
+    if custom.initial_capacity_log2() > MAX_CAPACITY_LOG2 {
+        return Err(format!(
+            "initial capacity log2 must be at most {}",
+            MAX_CAPACITY_LOG2
+        ));
+    }
+
+    Ok(())
+}
+
+/// Key distribution of a run. `HotKeys` hammers a single key unless
+/// `--distribution hot:<keys>` picks more.
+pub(crate) fn distribution(options: &Options) -> KeyDistribution {
+    match (options.workload, options.distribution) {
+        (WorkloadKind::HotKeys, KeyDistribution::Uniform) => KeyDistribution::Hot(1),
+        (_, distribution) => distribution,
+    }
+}
+
+/// Phases of the `Phased` workload when no `--phase` is given: fill the map,
+/// read it, then churn through it.
+const DEFAULT_PHASES: &[&str] = &[
+    "fill:insert=100:0.75",
+    "steady:read=95,update=5:1",
+    "churn:read=20,insert=40,remove=40:1",
+];
+
+/// Phases of a run, empty unless the workload is `Phased`. Lengths given in
+/// operations are scaled by `--operations`.
+pub(crate) fn phases(options: &Options) -> Vec<Phase> {
+    if !matches!(options.workload, WorkloadKind::Phased) {
+        return Vec::new();
+    }
+
+    let mut phases = if options.phase.is_empty() {
+        DEFAULT_PHASES
+            .iter()
+            .map(|phase| phase.parse().expect("invalid default phase"))
+            .collect()
+    } else {
+        options.phase.clone()
+    };
+
+    for phase in &mut phases {
+        if let Length::Operations(multiplier) = &mut phase.length {
+            *multiplier *= options.operations;
+        }
+    }
+    phases
+}
+
+/// Describes the workload of a run, as recorded in the output.
+pub(crate) fn label(options: &Options) -> String {
+    match options.workload {
+        WorkloadKind::Phased if !options.phase.is_empty() => {
+            let phases: Vec<_> = options.phase.iter().map(Phase::to_string).collect();
+            format!("Phased({})", phases.join(" "))
+        }
+        WorkloadKind::Custom => {
+            let custom = &options.mix;
+            let mix = custom.mix();
+            format!(
+                "Custom(read={} insert={} remove={} update={} upsert={} capacity=2^{} prefill={})",
+                mix.read,
+                mix.insert,
+                mix.remove,
+                mix.update,
+                mix.upsert,
+                custom.initial_capacity_log2(),
+                custom.prefill_fraction(),
+            )
+        }
+        kind => kind.name().into(),
+    }
+}
+
 fn read_heavy(threads: u32) -> Workload {
     let mix = Mix {
         read: 98,
@@ -33,9 +331,10 @@ fn read_heavy(threads: u32) -> Workload {
         upsert: 0,
     };
 
-    *Workload::new(threads as usize, mix)
+    Workload::new(threads as usize, mix)
         .initial_capacity_log2(25)
         .prefill_fraction(0.75)
+        .clone()
 }
 
 fn rapid_grow(threads: u32) -> Workload {
@@ -47,9 +346,10 @@ fn rapid_grow(threads: u32) -> Workload {
         upsert: 0,
     };
 
-    *Workload::new(threads as usize, mix)
+    Workload::new(threads as usize, mix)
         .initial_capacity_log2(25)
         .prefill_fraction(0.0)
+        .clone()
 }
 
 fn exchange(threads: u32) -> Workload {
@@ -61,9 +361,77 @@ fn exchange(threads: u32) -> Workload {
         upsert: 0,
     };
 
-    *Workload::new(threads as usize, mix)
+    Workload::new(threads as usize, mix)
         .initial_capacity_log2(25)
         .prefill_fraction(0.75)
+        .clone()
+}
+
+/// Counter aggregation: every event bumps the counter of its key, creating it
+/// the first time the key is seen.
+fn aggregate(threads: u32) -> Workload {
+    let mix = Mix {
+        read: 10,
+        insert: 0,
+        remove: 0,
+        update: 0,
+        upsert: 90,
+    };
+
+    Workload::new(threads as usize, mix)
+        .initial_capacity_log2(25)
+        .prefill_fraction(0.5)
+        .clone()
+}
+
+/// A cache filled on demand with get-or-insert-with, entries being evicted
+/// all the while.
+fn get_or_insert(threads: u32) -> Workload {
+    let mix = Mix {
+        read: 50,
+        insert: 0,
+        remove: 10,
+        update: 0,
+        upsert: 40,
+    };
+
+    Workload::new(threads as usize, mix)
+        .initial_capacity_log2(25)
+        .prefill_fraction(0.75)
+        .clone()
+}
+
+/// Shared counters: all threads read and update the same few keys, see
+/// `distribution`.
+fn hot_keys(threads: u32) -> Workload {
+    let mix = Mix {
+        read: 50,
+        insert: 0,
+        remove: 0,
+        update: 50,
+        upsert: 0,
+    };
+
+    Workload::new(threads as usize, mix)
+        .initial_capacity_log2(22)
+        .prefill_fraction(0.0)
+        .clone()
+}
+
+/// Changes the mix mid-run, see `phases`.
+fn phased(phases: Vec<Phase>, threads: u32) -> Workload {
+    Workload::new(threads as usize, phases[0].mix)
+        .initial_capacity_log2(25)
+        .prefill_fraction(0.0)
+        .phases(phases)
+        .clone()
+}
+
+fn custom(custom: &MixOptions, threads: u32) -> Workload {
+    Workload::new(threads as usize, custom.mix())
+        .initial_capacity_log2(custom.initial_capacity_log2())
+        .prefill_fraction(custom.prefill_fraction())
+        .clone()
 }
 
 pub(crate) fn create(options: &Options, threads: u32) -> Workload {
@@ -71,8 +439,28 @@ pub(crate) fn create(options: &Options, threads: u32) -> Workload {
         WorkloadKind::ReadHeavy => read_heavy(threads),
         WorkloadKind::Exchange => exchange(threads),
         WorkloadKind::RapidGrow => rapid_grow(threads),
+        WorkloadKind::Aggregate => aggregate(threads),
+        WorkloadKind::GetOrInsert => get_or_insert(threads),
+        WorkloadKind::HotKeys => hot_keys(threads),
+        WorkloadKind::Phased => phased(phases(options), threads),
+        WorkloadKind::Custom => custom(&options.mix, threads),
     };
 
-    workload.operations(options.operations);
+    workload
+        .operations(options.operations)
+        .latency_sample(options.latency_sample)
+        .distribution(distribution(options));
+    if let Some(duration) = options.duration {
+        workload.duration(duration);
+    }
+    if let Some(warmup) = options.warmup {
+        workload.warmup(warmup);
+    }
+    if options.timeout_secs > 0 {
+        workload.timeout(Duration::from_secs(options.timeout_secs));
+    }
+    if let Some(cpus) = affinity::placement(&options.pin, threads) {
+        workload.cpus(cpus);
+    }
     workload
 }